@@ -0,0 +1,507 @@
+use crate::contract::functions::{
+    argument_default, borrowed_argument, export_name, exported_methods, extract_arguments,
+    extract_return_type, has_caller_argument, has_state_argument, owned_return_type,
+    replace_self_in_types,
+};
+use crate::contract::options::ContractOptions;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{Ident, ItemImpl, Type};
+
+/// Generates the `__abi` export describing the callable surface of the contract.
+///
+/// The descriptor is computed at macro-expansion time from the exported methods and
+/// lists the name, the number of arguments, excluding the receiver and an injected
+/// state or caller, and the return type of each of them, sorted by export name so that
+/// the descriptor only changes with the exported methods. `Self` in return types is
+/// replaced with the struct name, and references cloned by the `auto_clone_returns`
+/// option with their owned type. Calling `__abi` returns the descriptor serialized as a
+/// `Vec<(String, u32, String)>`, so the contract crate must link `alloc`.
+///
+/// # Parameters
+/// - `impl_blocks`: The implementation blocks of the contract.
+/// - `struct_name`: The name of the public struct.
+/// - `options`: The options passed to the `#[contract]` attribute, which determine the
+///   export names.
+///
+/// # Returns
+/// A token stream representing the `__abi` function.
+///
+/// # Example
+/// For a contract exporting `read_value(&self) -> i64` and `add(&mut self, value: i64)`:
+/// ```ignore
+/// #[no_mangle]
+/// pub unsafe fn __abi(arg_len: u32) -> u32 {
+///     dusk_core::abi::wrap_call(arg_len, |(): ()| {
+///         alloc::vec![
+///             (alloc::string::String::from("add"), 1u32, alloc::string::String::from("()")),
+///             (alloc::string::String::from("read_value"), 0u32, alloc::string::String::from("i64")),
+///         ]
+///     })
+/// }
+/// ```
+pub fn generate_abi_function(
+    impl_blocks: &[ItemImpl],
+    struct_name: &Ident,
+    options: &ContractOptions,
+) -> TokenStream {
+    let entries: Vec<_> = method_descriptors(impl_blocks, struct_name, options)
+        .iter()
+        .map(|method| {
+            let name = &method.name;
+            let arity = method.arguments.len() as u32;
+            let output = type_string(&method.output);
+            quote! {
+                (
+                    alloc::string::String::from(#name),
+                    #arity,
+                    alloc::string::String::from(#output),
+                )
+            }
+        })
+        .collect();
+
+    quote! {
+        // A `no_mangle` function describing the methods exported by the contract.
+        #[no_mangle]
+        pub unsafe fn __abi(arg_len: u32) -> u32 {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| {
+                alloc::vec![#(#entries),*]
+            })
+        }
+    }
+}
+
+/// Generates the `CONTRACT_ABI_JSON` constant describing the exported methods as JSON.
+///
+/// The constant lists the name of the contract, and the name, the arguments and the
+/// return type of each exported method, in the order of the `__abi` descriptor. Types are
+/// rendered as in the `__abi` descriptor. Unlike `__abi`, the constant isn't exported to
+/// the VM, but can be written to disk by a build script for off-chain tooling.
+///
+/// # Example
+/// For a contract `Counter` exporting `add(&mut self, value: i64)`:
+/// ```ignore
+/// pub const CONTRACT_ABI_JSON: &str = r#"{"contract":"Counter","methods":[{"name":"add","args":[{"name":"value","type":"i64"}],"returns":"()"}]}"#;
+/// ```
+pub fn generate_abi_json(
+    impl_blocks: &[ItemImpl],
+    struct_name: &Ident,
+    options: &ContractOptions,
+) -> TokenStream {
+    let methods: Vec<_> = method_descriptors(impl_blocks, struct_name, options)
+        .iter()
+        .map(|method| {
+            let arguments: Vec<_> = method
+                .arguments
+                .iter()
+                .map(|(name, ty)| {
+                    format!(
+                        r#"{{"name":{},"type":{}}}"#,
+                        json_string(&name.unraw().to_string()),
+                        json_string(&type_string(ty))
+                    )
+                })
+                .collect();
+            format!(
+                r#"{{"name":{},"args":[{}],"returns":{}}}"#,
+                json_string(&method.name),
+                arguments.join(","),
+                json_string(&type_string(&method.output))
+            )
+        })
+        .collect();
+    let json = format!(
+        r#"{{"contract":{},"methods":[{}]}}"#,
+        json_string(&struct_name.unraw().to_string()),
+        methods.join(",")
+    );
+
+    quote! {
+        /// The methods exported by the contract, described as JSON.
+        pub const CONTRACT_ABI_JSON: &str = #json;
+    }
+}
+
+/// The description of an exported method, as listed by the ABI descriptors.
+struct MethodDescriptor {
+    /// The name under which the method is exported.
+    name: String,
+    /// The names and types of the arguments decoded from the call, excluding the
+    /// receiver and an injected state or caller.
+    arguments: Vec<(Ident, Type)>,
+    /// The type returned by the export.
+    output: Type,
+}
+
+/// Describes the exported methods, sorted by export name.
+///
+/// The wrappers are emitted in source order, but the descriptors are sorted so that
+/// reordering the methods doesn't change them, keeping snapshots of the ABI comparable
+/// across builds.
+///
+/// `Self` in argument and return types is replaced with the struct name, and references
+/// decoded owned by the `borrow_args` option or cloned by the `auto_clone_returns` option
+/// with their owned type. Arguments with a `#[default(...)]` are described as the
+/// `Option` they are decoded as.
+fn method_descriptors(
+    impl_blocks: &[ItemImpl],
+    struct_name: &Ident,
+    options: &ContractOptions,
+) -> Vec<MethodDescriptor> {
+    let mut descriptors: Vec<_> = impl_blocks
+        .iter()
+        .flat_map(|imp| exported_methods(imp).map(move |method| (imp, method)))
+        .map(|(imp, method)| {
+            let (mut names, mut types) = extract_arguments(&method.sig);
+            replace_self_in_types(&mut types, struct_name);
+            if has_state_argument(method) {
+                names.remove(0);
+                types.remove(0);
+            }
+            for (name, ty) in names.iter().zip(&mut types) {
+                if let Some(owned) = borrowed_argument(ty, options) {
+                    *ty = owned;
+                }
+                if argument_default(&method.sig, name).is_some() {
+                    *ty = syn::parse_quote! { Option<#ty> };
+                }
+            }
+            if has_caller_argument(method, options) {
+                names.remove(0);
+                types.remove(0);
+            }
+            let mut output = extract_return_type(&method.sig, struct_name);
+            if options.auto_clone_returns {
+                output = owned_return_type(&output).unwrap_or(output);
+            }
+            MethodDescriptor {
+                name: export_name(imp, method, options).unraw().to_string(),
+                arguments: names.into_iter().zip(types).collect(),
+                output,
+            }
+        })
+        .collect();
+    descriptors.sort_by(|a, b| a.name.cmp(&b.name));
+    descriptors
+}
+
+/// Generates the `__contract_name` export identifying the contract.
+///
+/// The name is the one of the public struct holding the state, not of the module, and
+/// is returned serialized as a `String`.
+///
+/// # Parameters
+/// - `struct_name`: The name of the public struct.
+///
+/// # Returns
+/// A token stream representing the `__contract_name` function.
+pub fn generate_name_function(struct_name: &Ident) -> TokenStream {
+    let name = struct_name.to_string();
+
+    quote! {
+        // A `no_mangle` function returning the name of the contract.
+        #[no_mangle]
+        pub unsafe fn __contract_name(arg_len: u32) -> u32 {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| alloc::string::String::from(#name))
+        }
+    }
+}
+
+/// Renders a type as a compact string, e.g. `Vec<u8>` rather than `Vec < u8 >`.
+///
+/// Whitespace is only kept between two tokens that would otherwise merge, such as in
+/// `dyn Trait`.
+fn type_string(ty: &Type) -> String {
+    let spaced = ty.to_token_stream().to_string();
+    let chars: Vec<char> = spaced.chars().collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+
+    let mut compact = String::with_capacity(chars.len());
+    for (i, c) in chars.iter().enumerate() {
+        if *c == ' ' {
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1).copied();
+            if !matches!((previous, next), (Some(p), Some(n)) if is_word(p) && is_word(n)) {
+                continue;
+            }
+        }
+        compact.push(*c);
+    }
+    compact
+}
+
+/// Renders a string as a JSON string literal, escaping quotes and backslashes.
+fn json_string(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            json.push('\\');
+        }
+        json.push(c);
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_abi_function, generate_abi_json};
+    use crate::contract::functions::{generate_public_functions, ExportContext};
+    use crate::contract::options::ContractOptions;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use quote::quote;
+    use syn::{parse_quote, Expr, ExprLit, Ident, ItemConst, ItemImpl, Lit};
+
+    #[test]
+    fn test_abi_descriptor_lists_names_and_arities() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn add(&mut self, value: i64) {
+                    self.value += value;
+                }
+
+                pub fn transfer(&mut self, from: u64, to: u64, amount: i64) {}
+
+                fn private_helper(&self) {}
+            }
+        };
+
+        let struct_name: Ident = parse_quote!(Counter);
+
+        let abi =
+            generate_abi_function(&[imp], &struct_name, &ContractOptions::default()).to_string();
+
+        let expected = quote! {
+            alloc::vec![
+                (
+                    alloc::string::String::from("add"),
+                    1u32,
+                    alloc::string::String::from("()"),
+                ),
+                (
+                    alloc::string::String::from("read_value"),
+                    0u32,
+                    alloc::string::String::from("i64"),
+                ),
+                (
+                    alloc::string::String::from("transfer"),
+                    3u32,
+                    alloc::string::String::from("()"),
+                )
+            ]
+        };
+        assert!(abi.contains(&expected.to_string()));
+        assert!(!abi.contains("private_helper"));
+    }
+
+    #[test]
+    fn test_abi_descriptors_are_sorted_by_export_name() {
+        let first: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn withdraw(&mut self, amount: i64) {}
+
+                pub fn deposit(&mut self, amount: i64) {}
+            }
+        };
+        let second: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn balance(&self) -> i64 {
+                    self.value
+                }
+            }
+        };
+        let impl_blocks = [first, second];
+        let struct_name: Ident = parse_quote!(Counter);
+        let options = ContractOptions::default();
+
+        let abi = generate_abi_function(&impl_blocks, &struct_name, &options).to_string();
+        let positions: Vec<_> = ["balance", "deposit", "withdraw"]
+            .iter()
+            .map(|name| {
+                abi.find(&quote! { String::from(#name) }.to_string())
+                    .unwrap()
+            })
+            .collect();
+        assert!(positions.is_sorted());
+
+        let json = generate_abi_json(&impl_blocks, &struct_name, &options).to_string();
+        let positions: Vec<_> = ["balance", "deposit", "withdraw"]
+            .iter()
+            .map(|name| json.find(name).unwrap())
+            .collect();
+        assert!(positions.is_sorted());
+
+        // The wrappers are emitted in source order
+        let cx = ExportContext {
+            struct_name: &struct_name,
+            state: quote!(STATE),
+            options: &options,
+            symbols: &[],
+        };
+        let wrappers: Vec<_> = generate_public_functions(&impl_blocks, &cx)
+            .unwrap()
+            .iter()
+            .map(|wrapper| wrapper.to_string())
+            .collect();
+        for (wrapper, name) in wrappers.iter().zip(["withdraw", "deposit", "balance"]) {
+            let name = Ident::new(name, proc_macro2::Span::call_site());
+            assert!(wrapper.contains(&quote! { pub unsafe fn #name }.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_abi_descriptor_renders_tuple_returns() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn pair(&self) -> (u64, bool) {
+                    (self.value, true)
+                }
+
+                pub fn single(&self) -> (u64,) {
+                    (self.value,)
+                }
+            }
+        };
+        let struct_name: Ident = parse_quote!(Counter);
+
+        let abi =
+            generate_abi_function(&[imp], &struct_name, &ContractOptions::default()).to_string();
+
+        assert!(abi.contains(&quote! { alloc::string::String::from("(u64,bool)") }.to_string()));
+        assert!(abi.contains(&quote! { alloc::string::String::from("(u64,)") }.to_string()));
+    }
+
+    #[test]
+    fn test_abi_json_lists_methods() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn transfer(&mut self, to: [u8; 32], amount: Vec<Self>) {}
+            }
+        };
+        let struct_name: Ident = parse_quote!(Counter);
+
+        let json = generate_abi_json(&[imp], &struct_name, &ContractOptions::default());
+
+        let expected = r#"{"contract":"Counter","methods":[{"name":"read_value","args":[],"returns":"i64"},{"name":"transfer","args":[{"name":"to","type":"[u8;32]"},{"name":"amount","type":"Vec<Counter>"}],"returns":"()"}]}"#;
+        assert_eq!(
+            json.to_string(),
+            quote! {
+                /// The methods exported by the contract, described as JSON.
+                pub const CONTRACT_ABI_JSON: &str = #expected;
+            }
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_abi_descriptors_name_type_aliases_as_written() {
+        // `type Amount = u64;` is declared in the module, and not resolved by the macro
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn scale(&self, amount: Amount, factor: Option<Amount>) -> Amount {
+                    amount
+                }
+            }
+        };
+        let impl_blocks = [imp];
+        let struct_name: Ident = parse_quote!(Counter);
+        let options = ContractOptions::default();
+
+        let abi = generate_abi_function(&impl_blocks, &struct_name, &options).to_string();
+        assert!(abi.contains(&quote! { alloc::string::String::from("Amount") }.to_string()));
+
+        let json: ItemConst =
+            syn::parse2(generate_abi_json(&impl_blocks, &struct_name, &options)).unwrap();
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(json),
+            ..
+        }) = *json.expr
+        else {
+            panic!("expected a string constant");
+        };
+        let expected = r#"{"name":"scale","args":[{"name":"amount","type":"Amount"},{"name":"factor","type":"Option<Amount>"}],"returns":"Amount"}"#;
+        assert!(json.value().contains(expected));
+    }
+
+    #[test]
+    fn test_abi_descriptor_rewrites_self_in_return_types() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                type Output = i64;
+
+                pub fn sum(&self) -> Self::Output {
+                    self.value
+                }
+
+                pub fn pair(&self) -> Vec<Self> {
+                    Vec::new()
+                }
+            }
+        };
+        let struct_name: Ident = parse_quote!(Counter);
+
+        let abi =
+            generate_abi_function(&[imp], &struct_name, &ContractOptions::default()).to_string();
+
+        assert!(
+            abi.contains(&quote! { alloc::string::String::from("Counter::Output") }.to_string())
+        );
+        assert!(abi.contains(&quote! { alloc::string::String::from("Vec<Counter>") }.to_string()));
+        assert!(!abi.contains("Self"));
+    }
+
+    #[test]
+    fn test_abi_descriptors_name_struct_returned_as_self() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn snapshot(&self) -> Self {
+                    Self { value: self.value }
+                }
+
+                pub fn merge(&self, other: Self) -> Option<Self> {
+                    None
+                }
+            }
+        };
+        let impl_blocks = [imp];
+        let struct_name: Ident = parse_quote!(Counter);
+        let options = ContractOptions::default();
+
+        let abi = generate_abi_function(&impl_blocks, &struct_name, &options).to_string();
+        let expected = quote! {
+            (
+                alloc::string::String::from("snapshot"),
+                0u32,
+                alloc::string::String::from("Counter"),
+            )
+        };
+        assert!(abi.contains(&expected.to_string()));
+
+        let json: ItemConst =
+            syn::parse2(generate_abi_json(&impl_blocks, &struct_name, &options)).unwrap();
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(json),
+            ..
+        }) = *json.expr
+        else {
+            panic!("expected a string constant");
+        };
+        let expected = r#"{"contract":"Counter","methods":[{"name":"merge","args":[{"name":"other","type":"Counter"}],"returns":"Option<Counter>"},{"name":"snapshot","args":[],"returns":"Counter"}]}"#;
+        assert_eq!(json.value(), expected);
+    }
+}
@@ -0,0 +1,249 @@
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{FnArg, Ident, Item, ItemMod, ItemTrait, Pat, ReturnType, TraitItem, TraitItemFn};
+
+/// Generates a caller struct for the trait named by the `gen_caller_for` option, whose
+/// methods call the methods of the trait on another contract.
+///
+/// The trait describes the interface of the called contract, and is declared in the
+/// contract module. Each method of the caller encodes its arguments as the generated
+/// wrappers decode them, i.e. as a tuple when there are several, and calls the export of
+/// the same name through `dusk_core::abi::call`, returning the result of the call or the
+/// error reported by the VM.
+///
+/// # Parameters
+/// - `trait_name`: The name of the trait describing the called contract.
+/// - `input_mod`: The contract module, declaring the trait.
+///
+/// # Errors
+/// - If the module doesn't declare the trait.
+/// - If a method of the trait is generic, or doesn't take `&self` or `&mut self`, as it
+///   can't be called on another contract.
+///
+/// # Example
+/// For `trait TokenApi { fn balance(&self, who: Address) -> u64; }`:
+/// ```ignore
+/// /// Calls the methods of `TokenApi` on another contract.
+/// pub struct TokenApiCaller {
+///     /// The contract called.
+///     pub contract: dusk_core::abi::ContractId,
+/// }
+///
+/// impl TokenApiCaller {
+///     /// Creates a caller for the given contract.
+///     pub const fn new(contract: dusk_core::abi::ContractId) -> Self {
+///         Self { contract }
+///     }
+///
+///     pub fn balance(&self, who: Address) -> Result<u64, dusk_core::abi::ContractError> {
+///         dusk_core::abi::call(self.contract, "balance", &who)
+///     }
+/// }
+/// ```
+pub fn generate_caller(trait_name: &Ident, input_mod: &ItemMod) -> syn::Result<TokenStream> {
+    let api = input_mod
+        .content
+        .iter()
+        .flat_map(|(_, items)| items)
+        .find_map(|item| match item {
+            Item::Trait(api) if api.ident == *trait_name => Some(api),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                trait_name,
+                format!("The contract module must declare the trait `{trait_name}` to generate a caller for it."),
+            )
+        })?;
+
+    let caller_name = format_ident!("{}Caller", trait_name);
+    let doc = format!(" Calls the methods of `{trait_name}` on another contract.");
+    let methods = methods(api)
+        .map(caller_method)
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #[doc = #doc]
+        pub struct #caller_name {
+            /// The contract called.
+            pub contract: dusk_core::abi::ContractId,
+        }
+
+        impl #caller_name {
+            /// Creates a caller for the given contract.
+            pub const fn new(contract: dusk_core::abi::ContractId) -> Self {
+                Self { contract }
+            }
+
+            #(#methods)*
+        }
+    })
+}
+
+/// Returns the methods declared by the trait, in source order.
+fn methods(api: &ItemTrait) -> impl Iterator<Item = &TraitItemFn> {
+    api.items.iter().filter_map(|item| match item {
+        TraitItem::Fn(method) => Some(method),
+        _ => None,
+    })
+}
+
+/// Generates the method of the caller calling the given method of the trait.
+///
+/// Arguments bound by a pattern other than an identifier, e.g. `_`, are named by their
+/// position.
+fn caller_method(method: &TraitItemFn) -> syn::Result<TokenStream> {
+    let sig = &method.sig;
+    let name = &sig.ident;
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            format!("The method `{name}` can't be called on another contract, as it is generic."),
+        ));
+    }
+    if !matches!(sig.inputs.first(), Some(FnArg::Receiver(receiver)) if receiver.reference.is_some())
+    {
+        return Err(syn::Error::new_spanned(
+            sig,
+            format!("The method `{name}` must take `&self` or `&mut self` to be called on another contract."),
+        ));
+    }
+
+    let (names, types): (Vec<_>, Vec<_>) = sig
+        .inputs
+        .iter()
+        .skip(1)
+        .enumerate()
+        .filter_map(|(i, arg)| match arg {
+            FnArg::Typed(pat_type) => {
+                let name = match &*pat_type.pat {
+                    Pat::Ident(pat_ident) => pat_ident.ident.clone(),
+                    _ => format_ident!("arg{}", i),
+                };
+                Some((name, &*pat_type.ty))
+            }
+            FnArg::Receiver(_) => None,
+        })
+        .unzip();
+    let output = match &sig.output {
+        ReturnType::Default => quote! { () },
+        ReturnType::Type(_, ty) => quote! { #ty },
+    };
+    // Encoded as the generated wrappers decode the arguments
+    let arguments = match names.as_slice() {
+        [name] => quote! { #name },
+        names => quote! { (#(#names),*) },
+    };
+    let export = name.unraw().to_string();
+
+    Ok(quote! {
+        pub fn #name(&self #(, #names: #types)*) -> Result<#output, dusk_core::abi::ContractError> {
+            dusk_core::abi::call(self.contract, #export, &#arguments)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate_caller;
+    use alloc::string::ToString;
+    use quote::quote;
+    use syn::{Ident, ItemMod};
+
+    fn token_api(methods: proc_macro2::TokenStream) -> ItemMod {
+        syn::parse_quote! {
+            pub mod exchange {
+                pub trait TokenApi {
+                    #methods
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_caller_calls_trait_method_on_contract() {
+        let input_mod = token_api(quote! {
+            fn balance(&self, who: Address) -> u64;
+        });
+        let trait_name: Ident = syn::parse_quote!(TokenApi);
+
+        let caller = generate_caller(&trait_name, &input_mod).unwrap();
+
+        let expected = quote! {
+            pub struct TokenApiCaller {
+                /// The contract called.
+                pub contract: dusk_core::abi::ContractId,
+            }
+
+            impl TokenApiCaller {
+                /// Creates a caller for the given contract.
+                pub const fn new(contract: dusk_core::abi::ContractId) -> Self {
+                    Self { contract }
+                }
+
+                pub fn balance(&self, who: Address) -> Result<u64, dusk_core::abi::ContractError> {
+                    dusk_core::abi::call(self.contract, "balance", &who)
+                }
+            }
+        };
+        assert!(caller.to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_caller_encodes_arguments_as_tuple() {
+        let input_mod = token_api(quote! {
+            fn transfer(&mut self, to: Address, _: u64);
+            fn supply(&self) -> u64;
+        });
+        let trait_name: Ident = syn::parse_quote!(TokenApi);
+
+        let caller = generate_caller(&trait_name, &input_mod)
+            .unwrap()
+            .to_string();
+
+        let transfer = quote! {
+            pub fn transfer(&self, to: Address, arg1: u64) -> Result<(), dusk_core::abi::ContractError> {
+                dusk_core::abi::call(self.contract, "transfer", &(to, arg1))
+            }
+        };
+        let supply = quote! {
+            pub fn supply(&self) -> Result<u64, dusk_core::abi::ContractError> {
+                dusk_core::abi::call(self.contract, "supply", &())
+            }
+        };
+        assert!(caller.contains(&transfer.to_string()));
+        assert!(caller.contains(&supply.to_string()));
+    }
+
+    #[test]
+    fn test_caller_requires_declared_trait() {
+        let input_mod = token_api(quote!());
+        let trait_name: Ident = syn::parse_quote!(NftApi);
+
+        let error = generate_caller(&trait_name, &input_mod).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "The contract module must declare the trait `NftApi` to generate a caller for it."
+        );
+    }
+
+    #[test]
+    fn test_caller_rejects_method_without_receiver() {
+        let input_mod = token_api(quote! {
+            fn decimals() -> u8;
+        });
+        let trait_name: Ident = syn::parse_quote!(TokenApi);
+
+        let error = generate_caller(&trait_name, &input_mod).err().unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "The method `decimals` must take `&self` or `&mut self` to be called on another contract."
+        );
+    }
+}
@@ -1,7 +1,38 @@
+use crate::contract::options::ContractOptions;
+use crate::contract::parser::{Constructor, StateInit};
+use crate::contract::transformation::ReplaceSelfWithStructName;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::ToString;
 use alloc::vec::Vec;
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::{FnArg, Ident, ImplItem, ItemImpl, Pat, Visibility};
+use quote::{format_ident, quote, quote_spanned};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{
+    Attribute, Expr, FnArg, GenericArgument, Ident, ImplItem, ImplItemFn, ItemFn, ItemImpl, LitStr,
+    Pat, PatType, PathArguments, ReturnType, Signature, Type, Visibility,
+};
+
+/// The maximum number of arguments decoded by a wrapper.
+///
+/// The arguments are deserialized by `wrap_call` as a tuple, and `rkyv` only implements
+/// its traits for tuples of up to 12 elements.
+const MAX_ARGUMENTS: usize = 12;
+
+/// The contract information shared by all generated `no_mangle` wrappers.
+pub struct ExportContext<'a> {
+    /// The name of the public struct.
+    pub struct_name: &'a Ident,
+    /// The expression through which the contract state is accessed.
+    pub state: TokenStream,
+    /// The options passed to the `#[contract]` attribute.
+    pub options: &'a ContractOptions,
+    /// The symbols exported by `no_mangle` functions declared by the user next to the
+    /// wrappers.
+    pub symbols: &'a [Ident],
+}
 
 /// Generates `no_mangle` functions for all public methods in the provided implementation blocks.
 ///
@@ -10,17 +41,302 @@ use syn::{FnArg, Ident, ImplItem, ItemImpl, Pat, Visibility};
 ///
 /// # Parameters
 /// - `impl_blocks`: A slice of `ItemImpl` representing the implementation blocks to process.
-/// - `mod_name`: The name of the module containing the contract.
+/// - `cx`: The contract information shared by the wrappers.
+///
+/// # Returns
+/// A vector of token streams representing all generated `no_mangle` functions, in source
+/// order: the `impl` blocks in the order they are declared, and the methods of each block
+/// in declaration order. The selectors of the `entry` option follow the same order, while
+/// the `__abi` descriptor is sorted by export name.
+///
+/// Private methods such as `private_helper` will not have wrappers generated. With the
+/// `entry` option, a single dispatch function is generated instead, see
+/// [`generate_dispatch_function`].
+///
+/// # Errors
+/// - If the signature of an exported method can't cross the VM boundary, see
+///   [`validate_signature`].
+/// - If a method is marked `#[only_owner]` without the `owner_field` option.
+pub fn generate_public_functions(
+    impl_blocks: &[ItemImpl],
+    cx: &ExportContext,
+) -> syn::Result<Vec<TokenStream>> {
+    validate_unique_exports(impl_blocks, cx)?;
+
+    if let Some(entry) = &cx.options.entry {
+        return Ok(generate_dispatch_function(impl_blocks, entry, cx)?
+            .into_iter()
+            .collect());
+    }
+
+    let mut functions = Vec::new();
+    for imp in impl_blocks {
+        functions.extend(generate_no_mangle_functions(imp, cx)?);
+    }
+    Ok(functions)
+}
+
+/// Ensures that no two exported methods are exported under the same name, as their
+/// `no_mangle` symbols would collide.
+///
+/// # Errors
+/// - If two exported methods share an export name, e.g. because two traits implemented
+///   by the state define a method with the same name without the `namespace_traits`
+///   option.
+/// - If a method is exported under the symbol of a `no_mangle` function of the user.
+/// - If an alias of a method collides with another export.
+/// - If the `#[export]` attribute of a method is malformed, see [`export_attribute`].
+fn validate_unique_exports(impl_blocks: &[ItemImpl], cx: &ExportContext) -> syn::Result<()> {
+    let mut names = BTreeMap::new();
+    for imp in impl_blocks {
+        for method in exported_methods(imp) {
+            let aliases = export_attribute(method)?.aliases;
+            for name in core::iter::once(export_name(imp, method, cx.options)).chain(aliases) {
+                if let Some(symbol) = cx
+                    .symbols
+                    .iter()
+                    .find(|symbol| symbol.unraw() == name.unraw())
+                {
+                    let mut error = syn::Error::new_spanned(
+                    &method.sig.ident,
+                    format!("The method `{}` is exported under the same symbol as a `no_mangle` function of the module. Rename one of them.", name.unraw()),
+                );
+                    error.combine(syn::Error::new_spanned(
+                        symbol,
+                        "the `no_mangle` function is declared here",
+                    ));
+                    return Err(error);
+                }
+                if let Some(first) = names.insert(name.unraw().to_string(), name.clone()) {
+                    let mut error = syn::Error::new_spanned(
+                    &method.sig.ident,
+                    format!("The method `{}` is exported more than once. Rename one of the methods, or set `#[contract(namespace_traits)]` if they belong to different traits.", name.unraw()),
+                );
+                    error.combine(syn::Error::new_spanned(
+                        &first,
+                        "the first export is generated here",
+                    ));
+                    return Err(error);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generates the `init` export for a constructor that initializes the state at runtime.
+///
+/// The host calls `init`, or the export named by the `init_name` option, on deployment
+/// with the constructor arguments. The wrapper decodes
+/// them, calls the `new` function and stores the result in `STATE`, boxed with the
+/// `boxed_state` option.
+///
+/// With the `init_guard` option, the wrapper records the initialization in a
+/// `static mut INITIALIZED: bool`, and aborts if the state was already initialized, so
+/// that calling `init` again doesn't reset the state.
+///
+/// # Parameters
+/// - `constructor`: The `new` function of the contract.
+/// - `cx`: The contract information shared by the wrappers.
+///
+/// # Returns
+/// The `init` wrapper, or `None` if the state is initialized inline.
+pub fn generate_init_function(
+    constructor: &Constructor,
+    cx: &ExportContext,
+) -> Option<TokenStream> {
+    if let StateInit::Inline(_) = constructor.init {
+        return None;
+    }
+
+    let struct_name = cx.struct_name;
+
+    let (arg_patterns, mut arg_types) = extract_arguments(&constructor.function.sig);
+    replace_self_in_types(&mut arg_types, struct_name);
+
+    let init_name = cx.options.init_name();
+    let ctor = &constructor.function.sig.ident;
+    let mut state = quote! { #struct_name::#ctor(#(#arg_patterns),*) };
+    if cx.options.boxed_state {
+        state = quote! { alloc::boxed::Box::new(#state) };
+    }
+    let call_block = match cx.options.init_guard {
+        true => {
+            let message = format!("the contract state is already initialized by `{init_name}`");
+            quote! {
+                {
+                    if INITIALIZED {
+                        panic!(#message);
+                    }
+                    STATE = Some(#state);
+                    INITIALIZED = true;
+                }
+            }
+        }
+        false => quote! {
+            {
+                STATE = Some(#state);
+            }
+        },
+    };
+
+    let body = wrap_call(&arg_patterns, &arg_types, call_block, false);
+    let wrapper = generate_no_mangle_wrapper(&init_name, None, body, cx.options);
+    Some(match cx.options.init_guard {
+        true => quote! {
+            // Whether the state was initialized by the `#init_name` export.
+            static mut INITIALIZED: bool = false;
+
+            #wrapper
+        },
+        false => wrapper,
+    })
+}
+
+/// Generates the `DeployArgs` struct with the `client` option, holding the arguments of a
+/// constructor initializing the state at runtime for the code deploying the contract.
+///
+/// The struct has a field for each argument of the constructor, and `into_init_arg`
+/// returns them as the `init` wrapper decodes them, i.e. as a tuple when there are
+/// several, to be serialized as the argument of the deployment.
 ///
 /// # Returns
-/// A vector of token streams representing all generated `no_mangle` functions.
+/// The `DeployArgs` struct, or `None` if the state is initialized inline, as the contract
+/// is then deployed without arguments.
+///
+/// # Example
+/// For `pub fn new(supply: u64, owner: ContractId) -> Self`:
+/// ```ignore
+/// /// The arguments of the `init` export, initializing the contract on deployment.
+/// pub struct DeployArgs {
+///     pub supply: u64,
+///     pub owner: ContractId,
+/// }
+///
+/// impl DeployArgs {
+///     /// Returns the arguments as decoded by the `init` export.
+///     pub fn into_init_arg(self) -> (u64, ContractId) {
+///         (self.supply, self.owner)
+///     }
+/// }
+/// ```
+pub fn generate_deploy_args(constructor: &Constructor, cx: &ExportContext) -> Option<TokenStream> {
+    if let StateInit::Inline(_) = constructor.init {
+        return None;
+    }
+
+    let (arg_patterns, mut arg_types) = extract_arguments(&constructor.function.sig);
+    replace_self_in_types(&mut arg_types, cx.struct_name);
+
+    let doc = format!(
+        " The arguments of the `{}` export, initializing the contract on deployment.",
+        cx.options.init_name()
+    );
+    let init_arg_doc = format!(
+        " Returns the arguments as decoded by the `{}` export.",
+        cx.options.init_name()
+    );
+    // Encoded as the `init` wrapper decodes the arguments
+    let (init_arg_type, init_arg) = match (arg_patterns.as_slice(), arg_types.as_slice()) {
+        ([pattern], [ty]) => (quote! { #ty }, quote! { self.#pattern }),
+        (patterns, types) => (quote! { (#(#types),*) }, quote! { (#(self.#patterns),*) }),
+    };
+
+    Some(quote! {
+        #[doc = #doc]
+        pub struct DeployArgs {
+            #(pub #arg_patterns: #arg_types,)*
+        }
+
+        impl DeployArgs {
+            #[doc = #init_arg_doc]
+            pub fn into_init_arg(self) -> #init_arg_type {
+                #init_arg
+            }
+        }
+    })
+}
+
+/// The names given to an exported method by its `#[export(...)]` attribute.
+#[derive(Default)]
+pub struct ExportAttribute {
+    /// The name under which the method is exported, in place of its own.
+    pub name: Option<Ident>,
+    /// The additional names under which the method is exported, e.g. to keep the name of
+    /// a method renamed in a new version of the contract.
+    pub aliases: Vec<Ident>,
+}
+
+/// Parses the `#[export(...)]` attribute of a method, e.g.
+/// `#[export(name = "transfer_from", alias = "transferFrom")]`.
+///
+/// `name` renames the export of the method, and each `alias`, which can be repeated,
+/// generates one more wrapper calling the method under the given name. Aliases aren't
+/// exported with the `entry` option, which dispatches on the index of the method.
 ///
-/// Private methods such as `private_helper` will not have wrappers generated.
-pub fn generate_public_functions(impl_blocks: &[ItemImpl], mod_name: &Ident) -> Vec<TokenStream> {
-    impl_blocks
+/// # Errors
+/// If the attribute has an argument other than `name` or `alias`, or a name isn't a
+/// valid identifier.
+pub fn export_attribute(method: &ImplItemFn) -> syn::Result<ExportAttribute> {
+    let mut export = ExportAttribute::default();
+    let attr = match method
+        .attrs
         .iter()
-        .flat_map(|imp| generate_no_mangle_functions(imp, mod_name))
-        .collect()
+        .find(|attr| attr.path().is_ident("export"))
+    {
+        Some(attr) => attr,
+        None => return Ok(export),
+    };
+
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("name") {
+            let name: LitStr = meta.value()?.parse()?;
+            export.name = Some(name.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("alias") {
+            let alias: LitStr = meta.value()?.parse()?;
+            export.aliases.push(alias.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unsupported `#[export]` argument, expected `name` or `alias`"))
+        }
+    })?;
+    Ok(export)
+}
+
+/// Returns the name under which a method of an `impl` block is exported.
+///
+/// This is the name of the method, prefixed with the name of the trait and an underscore
+/// for methods of trait implementations when the `namespace_traits` option is set, e.g.
+/// `TraitA_value`, or the name given by `#[export(name = "...")]`. A raw identifier such
+/// as `r#move` is kept raw, see [`generate_no_mangle_wrapper`] for the exported symbol.
+///
+/// # Parameters
+/// - `imp`: The `impl` block of the method.
+/// - `method`: The exported method.
+/// - `options`: The options passed to the `#[contract]` attribute.
+pub fn export_name(imp: &ItemImpl, method: &ImplItemFn, options: &ContractOptions) -> Ident {
+    // The attribute is validated with the exports, see `validate_unique_exports`
+    if let Ok(ExportAttribute {
+        name: Some(name), ..
+    }) = export_attribute(method)
+    {
+        return name;
+    }
+    let method_name = &method.sig.ident;
+    match &imp.trait_ {
+        Some((_, trait_path, _)) if options.namespace_traits => {
+            // A trait path always ends with a segment
+            let trait_name = &trait_path.segments.last().unwrap().ident;
+            format_ident!(
+                "{}_{}",
+                trait_name.unraw(),
+                method_name.unraw(),
+                span = method_name.span()
+            )
+        }
+        _ => method_name.clone(),
+    }
 }
 
 /// Generates `no_mangle` functions for a single `impl` block.
@@ -31,100 +347,2645 @@ pub fn generate_public_functions(impl_blocks: &[ItemImpl], mod_name: &Ident) ->
 ///
 /// # Parameters
 /// - `imp`: The `impl` block to process.
-/// - `mod_name`: The name of the module containing the contract.
+/// - `cx`: The contract information shared by the wrappers.
 ///
 /// # Returns
 /// A vector of token streams representing the generated `no_mangle` functions.
-fn generate_no_mangle_functions(imp: &ItemImpl, mod_name: &Ident) -> Vec<TokenStream> {
-    imp.items
-        .iter()
-        .filter_map(|item| match item {
-            ImplItem::Fn(method) if is_public_method(method) && method.sig.ident != "new" => Some(
-                generate_wrapper_function(method, imp.trait_.is_some(), mod_name),
-            ),
-            _ => None,
-        })
+fn generate_no_mangle_functions(
+    imp: &ItemImpl,
+    cx: &ExportContext,
+) -> syn::Result<Vec<TokenStream>> {
+    exported_methods(imp)
+        .map(|method| generate_wrapper_function(imp, method, cx))
         .collect()
 }
 
+/// Returns the methods of an `impl` block that are exported to the Dusk VM.
+///
+/// These are all public methods; the constructor was already removed from the block by
+/// the parser. Methods of trait implementations can't have a visibility and are all
+/// exported, as the parser only keeps the trait implementations opted in to exporting,
+/// see `is_contract_impl`.
+///
+/// # Parameters
+/// - `imp`: The `impl` block to process.
+pub fn exported_methods(imp: &ItemImpl) -> impl Iterator<Item = &ImplItemFn> {
+    let is_trait_impl = imp.trait_.is_some();
+    imp.items.iter().filter_map(move |item| match item {
+        ImplItem::Fn(method) if is_trait_impl || is_public_method(method) => Some(method),
+        _ => None,
+    })
+}
+
 /// Checks whether a method is public.
 ///
+/// Only methods declared with a plain `pub` are considered public and exported to the
+/// Dusk VM. Methods with a restricted visibility, such as `pub(crate)`, `pub(super)` or
+/// `pub(in path)`, are reachable from within the crate only and are therefore treated
+/// like private methods: no `no_mangle` wrapper is generated for them.
+///
 /// # Parameters
 /// - `method`: A reference to the method to check.
 ///
 /// # Returns
 /// `true` if the method is public, `false` otherwise.
-fn is_public_method(method: &syn::ImplItemFn) -> bool {
-    matches!(method.vis, Visibility::Public(_))
+fn is_public_method(method: &ImplItemFn) -> bool {
+    match method.vis {
+        Visibility::Public(_) => true,
+        Visibility::Restricted(_) | Visibility::Inherited => false,
+    }
 }
 
 /// Generates the `no_mangle` wrapper for a given method.
 ///
 /// This function creates a wrapper function that interacts with the Dusk VM. It handles both
-/// instance and static methods and prepares the function's arguments for the VM.
+/// instance and static methods and prepares the function's arguments for the VM. Since the
+/// wrapper is emitted outside of the `impl` block, `Self` in argument types is replaced
+/// with the struct name.
+///
+/// Methods of trait implementations are called through their fully qualified path, e.g.
+/// `<MyStruct as TraitA>::value(&STATE)`, so that traits defining methods with the same
+/// name don't make the call ambiguous.
+///
+/// With the `auto_clone_returns` option, a method returning a reference returns an owned
+/// copy of it instead, see [`clone_return`].
+///
+/// The first argument of a method is passed the caller of the contract instead of being
+/// decoded, when marked `#[caller]`, see [`has_caller_argument`]. The first argument of an
+/// associated function marked `#[uses_state]` is passed the state, see
+/// [`state_argument`], and its caller argument comes second.
+///
+/// A method marked `#[only_owner]` only runs when called by the owner stored in the field
+/// named by the `owner_field` option, and aborts otherwise. A method marked
+/// `#[validate(...)]` only runs with arguments accepted by its validator, see
+/// [`argument_validation`]. The hook named by the `pre` option runs before every method,
+/// see [`pre_hook`]. A method marked `#[payable]` claims the value transferred
+/// with the call first, see [`deposit_claim`].
+///
+/// With the `deny_panics` option, the method must return a `Result`, see
+/// [`require_result`].
+///
+/// A diverging method, e.g. `pub fn revert(&self) -> !`, has no return value to encode,
+/// see [`wrap_call`].
 ///
 /// # Parameters
+/// - `imp`: The `impl` block of the method.
 /// - `method`: The method for which to generate the wrapper.
-/// - `is_trait_impl`: Whether the method belongs to a trait implementation.
-/// - `mod_name`: The name of the module containing the contract.
+/// - `cx`: The contract information shared by the wrappers.
 ///
 /// # Returns
-/// A token stream representing the `no_mangle` wrapper function.
+/// A token stream representing the `no_mangle` wrapper function, followed by a wrapper
+/// for each alias of the method, see [`export_attribute`].
+///
+/// # Errors
+/// If the method decodes more than [`MAX_ARGUMENTS`] arguments, or its signature can't
+/// cross the VM boundary, see [`validate_signature`].
 fn generate_wrapper_function(
-    method: &syn::ImplItemFn,
-    is_trait_impl: bool,
-    mod_name: &Ident,
-) -> TokenStream {
-    let method_name = &method.sig.ident;
+    imp: &ItemImpl,
+    method: &ImplItemFn,
+    cx: &ExportContext,
+) -> syn::Result<TokenStream> {
+    let (arg_patterns, arg_types, call_block) = method_call(imp, method, cx)?;
+    let name = export_name(imp, method, cx.options);
+    let test_export = test_export(&name, method, &arg_patterns, &arg_types, &call_block, cx);
+    let aliases = export_attribute(method)?.aliases;
+    let guard = zero_value_guard(method, cx.options);
+    let body = wrap_call(
+        &arg_patterns,
+        &arg_types,
+        call_block,
+        returns_never(&method.sig),
+    );
+    let wrappers = core::iter::once(name).chain(aliases).map(|name| {
+        let body = quote! {
+            #guard
+            #body
+        };
+        generate_no_mangle_wrapper(&name, None, body, cx.options)
+    });
+    Ok(quote! {
+        #(#wrappers)*
+        #test_export
+    })
+}
 
-    let is_instance_method = method
-        .sig
-        .inputs
-        .iter()
-        .any(|arg| matches!(arg, FnArg::Receiver(_)));
+/// Generates the plain function calling an exported method natively with the
+/// `test_exports` option, so that host-side tests can exercise it without the VM.
+///
+/// The function is named `__call_` followed by the export name of the method, takes the
+/// arguments the wrapper decodes, e.g. `Option<T>` for an argument with a default, and
+/// runs the same call block as the wrapper, guards included, on `STATE`. As it borrows
+/// `STATE` mutably, it is `unsafe`: callers, such as tests running on several threads,
+/// must not call the functions of a contract concurrently.
+///
+/// # Example
+/// For `pub fn add(&mut self, value: i64) -> i64`:
+/// ```ignore
+/// /// # Safety
+/// /// ...
+/// pub unsafe fn __call_add(value: i64) -> i64 {
+///     STATE.add(value)
+/// }
+/// ```
+fn test_export(
+    name: &Ident,
+    method: &ImplItemFn,
+    arg_patterns: &[Ident],
+    arg_types: &[Type],
+    call_block: &TokenStream,
+    cx: &ExportContext,
+) -> Option<TokenStream> {
+    cx.options.test_exports.then(|| {
+        let function_name = format_ident!("__call_{}", name.unraw());
+        let mut output = extract_return_type(&method.sig, cx.struct_name);
+        if cx.options.auto_clone_returns {
+            output = owned_return_type(&output).unwrap_or(output);
+        }
+        let allow_non_snake_case = function_name
+            .to_string()
+            .contains(|c: char| c.is_uppercase())
+            .then(|| quote! { #[allow(non_snake_case)] });
+        let doc = format!(
+            " Calls the `{}` method natively, for host-side tests.",
+            name.unraw()
+        );
+        quote! {
+            #[doc = #doc]
+            ///
+            /// # Safety
+            /// The function accesses the `static mut` state of the contract. Callers must
+            /// serialize the calls to the functions of the contract, e.g. with a lock
+            /// shared by the tests, so that no two of them access the state at once.
+            #allow_non_snake_case
+            pub unsafe fn #function_name(#(#arg_patterns: #arg_types),*) -> #output {
+                #call_block
+            }
+        }
+    })
+}
+
+/// Generates the call of an exported method from its decoded arguments, see
+/// [`generate_wrapper_function`].
+///
+/// # Returns
+/// The names and types of the decoded arguments, and the call block run with them.
+fn method_call(
+    imp: &ItemImpl,
+    method: &ImplItemFn,
+    cx: &ExportContext,
+) -> syn::Result<(Vec<Ident>, Vec<Type>, TokenStream)> {
+    let passed = usize::from(has_state_argument(method));
+    validate_signature(&method.sig, passed, cx.options.borrow_args)?;
+    if cx.options.deny_panics {
+        require_result(&method.sig)?;
+    }
+    if cx.options.strict {
+        reject_ignored_signature(&method.sig)?;
+    }
+    reject_misplaced_caller(method)?;
+
+    let ExportContext {
+        struct_name, state, ..
+    } = cx;
+
+    let method_name = &method.sig.ident;
+    let receiver = method.sig.receiver();
+    let impl_type = impl_type(imp, struct_name);
 
     // Process arguments, skipping `self` for instance methods
-    let (arg_patterns, arg_types): (Vec<_>, Vec<_>) = method
-        .sig
-        .inputs
+    let (arg_patterns, mut arg_types) = extract_arguments(&method.sig);
+    replace_self_in_types(&mut arg_types, struct_name);
+
+    // Arguments taken by shared reference are decoded owned and passed borrowed
+    let call_args: Vec<_> = arg_patterns
         .iter()
+        .zip(&mut arg_types)
         .enumerate()
-        .filter_map(|(i, arg)| {
-            if i == 0 && is_instance_method {
-                // Skip `self`
-                None
-            } else if let FnArg::Typed(pat_type) = arg {
-                // Extract the name from the pattern
-                if let Pat::Ident(pat_ident) = *pat_type.pat.clone() {
-                    Some((pat_ident.ident.clone(), pat_type.ty.clone()))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .unzip();
+        .map(
+            |(i, (pattern, ty))| match borrowed_argument(ty, cx.options) {
+                Some(owned) if i >= passed => {
+                    *ty = owned;
+                    quote! { &#pattern }
+                }
+                _ => quote! { #pattern },
+            },
+        )
+        .collect();
 
     // Generate the call block (state-based or static)
-    let call_block = if is_instance_method {
-        if is_trait_impl {
+    let call_block = match (&imp.trait_, receiver) {
+        (Some((_, trait_path, _)), receiver) => {
+            let receiver = receiver.map(|receiver| match receiver.mutability {
+                Some(_) => quote! { &mut #state },
+                None => quote! { &#state },
+            });
+            let args = receiver.into_iter().chain(call_args);
             quote! {
-                <#mod_name::STATE as #method.sig.ident>::#method_name(#mod_name::STATE, #(#arg_patterns),*)
+                <#impl_type as #trait_path>::#method_name(#(#args),*)
             }
-        } else {
-            quote! { #mod_name::STATE.#method_name(#(#arg_patterns),*) }
         }
+        (None, Some(_)) => quote! { #state.#method_name(#(#call_args),*) },
+        (None, None) => quote! { #impl_type::#method_name(#(#call_args),*) },
+    };
+
+    let call_block = clone_return(method, call_block, cx.options)?;
+
+    // The state is passed by the wrapper rather than decoded from the arguments
+    let (state_argument, arg_patterns, arg_types) = match state_argument(method, cx)? {
+        Some(state_argument) => (
+            Some(state_argument),
+            arg_patterns[1..].to_vec(),
+            arg_types[1..].to_vec(),
+        ),
+        None => (None, arg_patterns, arg_types),
+    };
+
+    // The caller is passed by the VM rather than decoded from the arguments
+    let (caller, arg_patterns, mut arg_types) = if has_caller_argument(method, cx.options) {
+        let caller = &arg_patterns[0];
+        (
+            Some(quote! { let #caller = dusk_core::abi::caller(); }),
+            arg_patterns[1..].to_vec(),
+            arg_types[1..].to_vec(),
+        )
     } else {
-        quote! { #mod_name::#method_name(#(#arg_patterns),*) }
+        (None, arg_patterns, arg_types)
     };
+    validate_arity(&method.sig, arg_types.len())?;
 
-    // Generate the wrapper function
-    quote! {
-        // A `no_mangle` wrapper for the `#method_name` method.
+    // Arguments with a default are decoded as `Option`, after checking the payable value
+    let deposit_claim = deposit_claim(method, &arg_patterns, &arg_types)?;
+    let defaults = argument_defaults(&method.sig, &arg_patterns, &mut arg_types)?;
+
+    // Guard the call for methods restricted to the owner or validating their arguments,
+    // and claim the deposit of payable methods
+    let guards: Vec<_> = [
+        state_argument,
+        caller,
+        defaults,
+        pre_hook(method, cx),
+        owner_guard(method, cx)?,
+        argument_validation(method, &impl_type, &arg_patterns, cx.options)?,
+        deposit_claim,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    let call_block = if guards.is_empty() {
+        call_block
+    } else {
+        quote! {
+            {
+                #(#guards)*
+                #call_block
+            }
+        }
+    };
+
+    Ok((arg_patterns, arg_types, call_block))
+}
+
+/// Returns the type through which the associated functions of an `impl` block are
+/// called.
+///
+/// This is the struct name, keeping the generic arguments of the self type of the block
+/// as a turbofish, e.g. `MyStruct::<u32>` for `impl MyStruct<u32>`, so that they aren't
+/// replaced with the defaults of the struct.
+fn impl_type(imp: &ItemImpl, struct_name: &Ident) -> TokenStream {
+    let arguments = match &*imp.self_ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .filter(|segment| segment.ident == *struct_name)
+            .and_then(|segment| match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => Some(&arguments.args),
+                _ => None,
+            }),
+        _ => None,
+    };
+    match arguments {
+        Some(arguments) => quote! { #struct_name::<#arguments> },
+        None => quote! { #struct_name },
+    }
+}
+
+/// Checks whether an associated function is marked `#[uses_state]`, in which case the
+/// wrapper passes it the state as its first argument, see [`state_argument`].
+pub fn has_state_argument(method: &ImplItemFn) -> bool {
+    method
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("uses_state"))
+}
+
+/// Generates the binding of the first argument of an associated function marked
+/// `#[uses_state]` to the contract state, which the wrapper passes instead of decoding
+/// it.
+///
+/// This lets a function that doesn't take `self` read or modify the state, e.g.
+/// `#[uses_state] pub fn total(state: &Self, extra: u64) -> u64`. A `&mut` argument is
+/// passed the state mutably.
+///
+/// # Errors
+/// - If the function takes `self`, as the state is then already its receiver.
+/// - If the first argument isn't a reference bound by a name.
+fn state_argument(method: &ImplItemFn, cx: &ExportContext) -> syn::Result<Option<TokenStream>> {
+    let attr = match method
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("uses_state"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let name = method.sig.ident.unraw();
+    if let Some(receiver) = method.sig.receiver() {
+        return Err(syn::Error::new_spanned(
+            receiver,
+            format!("`#[uses_state]` passes the state to associated functions without `self`, but `{name}` already takes the state as `self`."),
+        ));
+    }
+    let first = match method.sig.inputs.first() {
+        Some(FnArg::Typed(pat_type)) => match (&*pat_type.pat, &*pat_type.ty) {
+            (Pat::Ident(pat), Type::Reference(reference)) => Some((&pat.ident, reference)),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (pattern, reference) = match first {
+        Some(first) => first,
+        None => {
+            return Err(syn::Error::new_spanned(
+                attr,
+                format!("`#[uses_state]` requires the first argument of `{name}` to be a reference to the state, e.g. `state: &Self`."),
+            ));
+        }
+    };
+
+    let state = &cx.state;
+    Ok(Some(match reference.mutability {
+        Some(_) => quote! { let #pattern = &mut #state; },
+        None => quote! { let #pattern = &#state; },
+    }))
+}
+
+/// Checks whether the first decoded argument of a method is the caller of the contract,
+/// which the wrapper passes from `dusk_core::abi::caller()` instead of decoding it.
+///
+/// This is the case for an argument marked `#[caller]`, e.g.
+/// `pub fn vote(&mut self, #[caller] caller: Option<ContractId>, choice: u8)`, and for an
+/// argument named `caller` with the `caller_arg` option. The first decoded argument of a
+/// function marked `#[uses_state]` is its second one, after the state.
+pub fn has_caller_argument(method: &ImplItemFn, options: &ContractOptions) -> bool {
+    let position = usize::from(has_state_argument(method));
+    match typed_arguments(&method.sig).nth(position) {
+        Some(pat_type) => {
+            let is_marked = pat_type
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("caller"));
+            let is_named = matches!(&*pat_type.pat, Pat::Ident(pat) if pat.ident == "caller");
+            matches!(&*pat_type.pat, Pat::Ident(_)) && (is_marked || options.caller_arg && is_named)
+        }
+        None => false,
+    }
+}
+
+/// Rejects a `#[caller]` attribute on an argument other than the first decoded one, which
+/// would otherwise be decoded from the call and let callers pass any caller.
+///
+/// # Errors
+/// If an argument marked `#[caller]` isn't the first decoded argument of the method. The
+/// error is spanned on the attribute.
+fn reject_misplaced_caller(method: &ImplItemFn) -> syn::Result<()> {
+    let position = usize::from(has_state_argument(method));
+    let misplaced = typed_arguments(&method.sig)
+        .enumerate()
+        .filter(|(i, _)| *i != position)
+        .flat_map(|(_, pat_type)| &pat_type.attrs)
+        .find(|attr| attr.path().is_ident("caller"));
+    match misplaced {
+        Some(attr) => Err(syn::Error::new_spanned(
+            attr,
+            format!(
+                "`#[caller]` must be on the first argument decoded from the call of `{}`, after `self` or the state passed by `#[uses_state]`.",
+                method.sig.ident.unraw()
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Returns the typed arguments of a signature, skipping its receiver.
+fn typed_arguments(sig: &Signature) -> impl Iterator<Item = &PatType> {
+    sig.inputs.iter().filter_map(|input| match input {
+        FnArg::Typed(pat_type) => Some(pat_type),
+        FnArg::Receiver(_) => None,
+    })
+}
+
+/// Returns the `#[default(...)]` attribute of the argument of a signature with the given
+/// name, if any.
+pub fn argument_default<'a>(sig: &'a Signature, name: &Ident) -> Option<&'a Attribute> {
+    sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(pat_type) if matches!(&*pat_type.pat, Pat::Ident(pat) if pat.ident == *name) => {
+            pat_type
+                .attrs
+                .iter()
+                .find(|attr| attr.path().is_ident("default"))
+        }
+        _ => None,
+    })
+}
+
+/// Generates the substitution of the defaults of the arguments marked `#[default(...)]`.
+///
+/// Such an argument is decoded as an `Option` of its type, and replaced with the default
+/// when `None` before calling the method, e.g. `#[default(1)] times: u32` is decoded as
+/// an `Option<u32>` and the method is passed `times.unwrap_or_else(|| 1)`. The default is
+/// only evaluated if the argument is `None`.
+///
+/// # Errors
+/// If the attribute doesn't give the default value.
+fn argument_defaults(
+    sig: &Signature,
+    arg_patterns: &[Ident],
+    arg_types: &mut [Type],
+) -> syn::Result<Option<TokenStream>> {
+    let mut defaults = Vec::new();
+    for (pattern, ty) in arg_patterns.iter().zip(arg_types) {
+        let attr = match argument_default(sig, pattern) {
+            Some(attr) => attr,
+            None => continue,
+        };
+        let default: Expr = attr.parse_args().map_err(|_| {
+            syn::Error::new_spanned(
+                attr,
+                "`#[default]` must give the default value of the argument, e.g. `#[default(0)]`.",
+            )
+        })?;
+        *ty = syn::parse_quote! { Option<#ty> };
+        defaults.push(quote! { let #pattern = #pattern.unwrap_or_else(|| #default); });
+    }
+    Ok((!defaults.is_empty()).then(|| quote! { #(#defaults)* }))
+}
+
+/// Generates the single `entry` export dispatching to the exported methods.
+///
+/// With the `entry` option, the methods aren't exported individually. Instead, the entry
+/// is called like any other export, with the length of its arguments, and dispatches on
+/// a selector, the index of the method in declaration order across the `impl` blocks.
+/// The argument buffer starts with the selector, a little-endian `u32` padded to 8 bytes
+/// so that the archived arguments of the method following it stay aligned. The entry
+/// reads the selector from the first 4 bytes, then decodes the arguments of the selected
+/// method from the whole buffer, as `wrap_call` finds the archived arguments at its end.
+/// It panics for an unknown selector.
+///
+/// As `dusk_core::abi` doesn't expose the argument buffer, the selector is read through
+/// `wrap_call` as a `[u8; 4]`, whose archive is the raw bytes, and converted with
+/// `u32::from_le_bytes`, so that its byte order doesn't depend on the archive format.
+/// The closure returns `()`, which doesn't overwrite the buffer, so the arguments are
+/// still in place for the selected method.
+///
+/// With `selector = "keccak"`, the selector of a method is derived from the name under
+/// which it would be exported instead, see [`SelectorScheme`](crate::contract::selector::SelectorScheme).
+///
+/// # Parameters
+/// - `impl_blocks`: The implementation blocks of the contract.
+/// - `entry`: The name of the dispatch export.
+/// - `cx`: The contract information shared by the wrappers.
+///
+/// # Returns
+/// The dispatch function, or `None` if the contract doesn't export any method.
+///
+/// # Example
+/// For a contract exporting `read_value(&self) -> i64` and `add(&mut self, value: i64)`:
+/// ```ignore
+/// #[no_mangle]
+/// pub unsafe fn call(arg_len: u32) -> u32 {
+///     let selector = core::cell::Cell::new(0u32);
+///     dusk_core::abi::wrap_call(4, |bytes: [u8; 4]| selector.set(u32::from_le_bytes(bytes)));
+///     match selector.get() {
+///         0u32 => dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.read_value()),
+///         1u32 => dusk_core::abi::wrap_call(arg_len, |(value): (i64)| STATE.add(value)),
+///         _ => panic!("unknown method selector"),
+///     }
+/// }
+/// ```
+///
+/// # Errors
+/// - If the call of a method can't be generated, see [`generate_wrapper_function`].
+/// - If two methods have the same selector.
+fn generate_dispatch_function(
+    impl_blocks: &[ItemImpl],
+    entry: &Ident,
+    cx: &ExportContext,
+) -> syn::Result<Option<TokenStream>> {
+    let scheme = cx.options.selector.unwrap_or_default();
+    let mut selectors = BTreeMap::new();
+    let mut arms = Vec::new();
+    let mut test_exports = Vec::new();
+    for (index, (imp, method)) in impl_blocks
+        .iter()
+        .flat_map(|imp| exported_methods(imp).map(move |method| (imp, method)))
+        .enumerate()
+    {
+        let (arg_patterns, arg_types, call_block) = method_call(imp, method, cx)?;
+        let name = export_name(imp, method, cx.options);
+        test_exports.extend(test_export(
+            &name,
+            method,
+            &arg_patterns,
+            &arg_types,
+            &call_block,
+            cx,
+        ));
+        let selector = scheme.selector(index, &name.unraw().to_string());
+        if let Some(first) = selectors.insert(selector, name.clone()) {
+            let mut error = syn::Error::new_spanned(
+                &method.sig.ident,
+                format!("The methods `{}` and `{}` have the same selector `{selector:#010x}`. Rename one of them.", first.unraw(), name.unraw()),
+            );
+            error.combine(syn::Error::new_spanned(
+                &first,
+                "the first method with this selector is declared here",
+            ));
+            return Err(error);
+        }
+        let log = call_log(&name, cx.options);
+        let guard = zero_value_guard(method, cx.options);
+        let body = wrap_call(
+            &arg_patterns,
+            &arg_types,
+            call_block,
+            returns_never(&method.sig),
+        );
+        arms.push(quote! {
+            #selector => {
+                #log
+                #guard
+                #body
+            }
+        });
+    }
+    if arms.is_empty() {
+        return Ok(None);
+    }
+
+    let keep = kept_export(entry, quote! { unsafe fn(u32) -> u32 }, cx.options);
+    Ok(Some(quote! {
+        // A `no_mangle` function dispatching to the exported methods.
         #[no_mangle]
-        pub unsafe fn #method_name(arg_len: u32) -> u32 {
-            dusk_core::abi::wrap_call(arg_len, |(#(#arg_patterns),*): (#(#arg_types),*)| #call_block)
+        pub unsafe fn #entry(arg_len: u32) -> u32 {
+            let selector = core::cell::Cell::new(0u32);
+            dusk_core::abi::wrap_call(4, |bytes: [u8; 4]| selector.set(u32::from_le_bytes(bytes)));
+            match selector.get() {
+                #(#arms)*
+                _ => panic!("unknown method selector"),
+            }
+        }
+        #keep
+        #(#test_exports)*
+    }))
+}
+
+/// Generates `no_mangle` functions for the public free functions of the module.
+///
+/// Each wrapper decodes the arguments of the function and calls it. As the wrapper is
+/// emitted next to the function, it is named `__export_` followed by the name of the
+/// function, and exported under the name of the function with `#[export_name]`.
+/// Wrappers are only generated when the `export_fns` option is set.
+///
+/// # Parameters
+/// - `functions`: The public free functions of the module.
+/// - `options`: The options passed to the `#[contract]` attribute.
+///
+/// # Returns
+/// A vector of token streams representing the generated `no_mangle` functions.
+///
+/// # Errors
+/// If the signature of a function can't cross the VM boundary, see
+/// [`validate_signature`].
+pub fn generate_free_functions(
+    functions: &[ItemFn],
+    options: &ContractOptions,
+) -> syn::Result<Vec<TokenStream>> {
+    functions
+        .iter()
+        .map(|func| {
+            validate_signature(&func.sig, 0, false)?;
+
+            let function_name = &func.sig.ident;
+            let wrapper_name = format_ident!("__export_{}", function_name.unraw());
+            let (arg_patterns, arg_types) = extract_arguments(&func.sig);
+            validate_arity(&func.sig, arg_types.len())?;
+            let call_block = quote! { #function_name(#(#arg_patterns),*) };
+            let body = wrap_call(
+                &arg_patterns,
+                &arg_types,
+                call_block,
+                returns_never(&func.sig),
+            );
+            Ok(generate_no_mangle_wrapper(
+                &wrapper_name,
+                Some(function_name),
+                body,
+                options,
+            ))
+        })
+        .collect()
+}
+
+/// Generates the call of the hook named by the `pre` option, run before every method.
+///
+/// The hook is a method of the state taking `&self` and returning a `Result<(), E>` with
+/// `E: Debug`, e.g. `fn check_not_paused(&self) -> Result<(), Error>`, and aborts the call
+/// by returning an error. With the `deny_panics` option, the error is returned by the
+/// wrapper instead, converted with `?`.
+fn pre_hook(method: &ImplItemFn, cx: &ExportContext) -> Option<TokenStream> {
+    let hook = cx.options.pre.as_ref()?;
+    let struct_name = cx.struct_name;
+    let state = &cx.state;
+    if cx.options.deny_panics {
+        return Some(quote! {
+            #struct_name::#hook(&#state)?;
+        });
+    }
+
+    let message = format!(
+        "the `pre` hook `{hook}` rejected the call to `{}`",
+        method.sig.ident.unraw()
+    );
+    Some(quote! {
+        #struct_name::#hook(&#state).expect(#message);
+    })
+}
+
+/// Generates the check aborting the call of an `#[only_owner]` method by anyone but the
+/// owner of the contract.
+///
+/// The caller reported by the VM is compared to the `ContractId` stored in the state field
+/// named by the `owner_field` option.
+///
+/// # Errors
+/// If the method is marked `#[only_owner]` but the `owner_field` option isn't set.
+fn owner_guard(method: &ImplItemFn, cx: &ExportContext) -> syn::Result<Option<TokenStream>> {
+    let attr = match method
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("only_owner"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let owner_field = match &cx.options.owner_field {
+        Some(owner_field) => owner_field,
+        None => {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`#[only_owner]` requires the field holding the owner to be named with `#[contract(owner_field = \"...\")]`.",
+            ));
+        }
+    };
+
+    let state = &cx.state;
+    let message = format!("only the owner can call `{}`", method.sig.ident.unraw());
+    Ok(Some(quote! {
+        assert!(dusk_core::abi::caller() == Some(#state.#owner_field), #message);
+    }))
+}
+
+/// Wraps the call of a method returning a reference into state in a call to `to_owned`,
+/// when the `auto_clone_returns` option is set.
+///
+/// References can't be serialized by `wrap_call`, so a `&str` is returned as a `String`
+/// and a `&[T]` as a `Vec<T>`, which requires the contract crate to link `alloc`.
+///
+/// # Errors
+/// If the option is set and the method returns a reference to a type other than `str` or
+/// a slice, as there's no owned type known to be returned in its place.
+fn clone_return(
+    method: &ImplItemFn,
+    call_block: TokenStream,
+    options: &ContractOptions,
+) -> syn::Result<TokenStream> {
+    if !options.auto_clone_returns {
+        return Ok(call_block);
+    }
+    match &method.sig.output {
+        ReturnType::Type(_, ty) if matches!(**ty, Type::Reference(_)) => {
+            if owned_return_type(ty).is_none() {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "`auto_clone_returns` only supports methods returning `&str` or `&[T]`. Return an owned value instead.",
+                ));
+            }
+            Ok(quote! { alloc::borrow::ToOwned::to_owned(#call_block) })
+        }
+        _ => Ok(call_block),
+    }
+}
+
+/// Returns the owned type returned in place of a `&str` or `&[T]` reference with the
+/// `auto_clone_returns` option, i.e. `String` or `Vec<T>`.
+pub fn owned_return_type(ty: &Type) -> Option<Type> {
+    let reference = match ty {
+        Type::Reference(reference) => reference,
+        _ => return None,
+    };
+    match &*reference.elem {
+        Type::Path(path) if path.qself.is_none() && path.path.is_ident("str") => {
+            Some(syn::parse_quote!(alloc::string::String))
+        }
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            Some(syn::parse_quote!(alloc::vec::Vec<#elem>))
+        }
+        _ => None,
+    }
+}
+
+/// Generates the validation of the arguments of a method marked `#[validate(...)]`.
+///
+/// The attribute names an associated function of the state taking a reference to each
+/// argument of the method, in order, and returning a `Result<(), E>` with `E: Debug`,
+/// e.g. `fn check_transfer(to: &u64, amount: &u64) -> Result<(), &'static str>` for
+/// `#[validate(check_transfer)] pub fn transfer(&mut self, to: u64, amount: u64)`. The call
+/// aborts if it returns an error.
+///
+/// With the `deny_panics` option, the error is returned by the wrapper instead, converted
+/// with `?` into the error of the `Result` returned by the method.
+///
+/// # Errors
+/// If the attribute doesn't name the validator.
+fn argument_validation(
+    method: &ImplItemFn,
+    impl_type: &TokenStream,
+    arg_patterns: &[Ident],
+    options: &ContractOptions,
+) -> syn::Result<Option<TokenStream>> {
+    let attr = match method
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("validate"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let validator: Ident = attr.parse_args().map_err(|_| {
+        syn::Error::new_spanned(
+            attr,
+            "`#[validate]` must name the function validating the arguments, e.g. `#[validate(check_transfer)]`.",
+        )
+    })?;
+
+    if options.deny_panics {
+        return Ok(Some(quote! {
+            #impl_type::#validator(#(&#arg_patterns),*)?;
+        }));
+    }
+
+    let message = format!("invalid arguments for `{}`", method.sig.ident.unraw());
+    Ok(Some(quote! {
+        #impl_type::#validator(#(&#arg_patterns),*).expect(#message);
+    }))
+}
+
+/// Generates the claim of the value transferred to a `#[payable]` method.
+///
+/// A payable method takes the transferred value as its first argument, e.g.
+/// `pub fn stake(&mut self, value: u64)`. Before calling the method, the wrapper claims
+/// this value from the deposit of the transaction by calling `deposit` on the transfer
+/// contract, which aborts the call if the deposit doesn't match. Methods that aren't
+/// payable leave the deposit to be refunded, or check that no value is deposited with the
+/// `deposit_query` option, see [`zero_value_guard`].
+///
+/// # Errors
+/// If the method is marked `#[payable]` but its first argument isn't a `u64`.
+fn deposit_claim(
+    method: &ImplItemFn,
+    arg_patterns: &[Ident],
+    arg_types: &[Type],
+) -> syn::Result<Option<TokenStream>> {
+    let attr = match method
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("payable"))
+    {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+
+    let value = match (arg_patterns.first(), arg_types.first()) {
+        (Some(value), Some(Type::Path(ty))) if ty.qself.is_none() && ty.path.is_ident("u64") => {
+            value
         }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`#[payable]` methods must take the transferred value as their first argument, e.g. `value: u64`.",
+            ));
+        }
+    };
+
+    Ok(Some(quote! {
+        dusk_core::abi::call::<_, ()>(dusk_core::transfer::TRANSFER_CONTRACT, "deposit", &#value)
+            .expect("claiming the deposit of the transaction must succeed");
+    }))
+}
+
+/// Generates the check that no value is transferred to a method that isn't `#[payable]`,
+/// with the `deposit_query` option.
+///
+/// The deposit of a transaction is meant for the method the transaction calls, through
+/// the transfer contract. By default, a method that isn't payable doesn't claim it, and
+/// the transfer contract refunds it. As claiming a deposit of zero fails when nothing is
+/// deposited, the check instead calls the query named by the option on the transfer
+/// contract, which returns the deposit of the transaction, and aborts the call if it isn't
+/// zero. The check runs before the arguments are decoded. Calls made by other contracts or
+/// by the host aren't checked, as their transaction's deposit is meant for another method.
+fn zero_value_guard(method: &ImplItemFn, options: &ContractOptions) -> Option<TokenStream> {
+    let query = options.deposit_query.as_ref()?;
+    let is_payable = method
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("payable"));
+    (!is_payable).then(|| {
+        quote! {
+            if dusk_core::abi::caller() == Some(dusk_core::transfer::TRANSFER_CONTRACT) {
+                let deposit = dusk_core::abi::call::<_, u64>(dusk_core::transfer::TRANSFER_CONTRACT, #query, &())
+                    .expect("querying the deposit of the transaction must succeed");
+                assert!(deposit == 0, "the method isn't payable, so the transaction must not deposit a value");
+            }
+        }
+    })
+}
+
+/// Returns the owned type decoded for an argument taken by shared reference with the
+/// `borrow_args` option, e.g. `Request` for `req: &Request`.
+///
+/// The wrapper decodes the owned value and passes a reference to it to the method, which
+/// borrows it for the duration of the call. Arguments taken by `&mut` are still rejected,
+/// see [`validate_signature`].
+pub fn borrowed_argument(ty: &Type, options: &ContractOptions) -> Option<Type> {
+    match ty {
+        Type::Reference(reference) if options.borrow_args && reference.mutability.is_none() => {
+            Some((*reference.elem).clone())
+        }
+        _ => None,
+    }
+}
+
+/// Validates that the signature of an exported function can cross the VM boundary.
+///
+/// The first `passed` arguments aren't decoded but passed by the wrapper, such as the
+/// state of a function marked `#[uses_state]`, and can be references. With
+/// `borrow_args`, arguments taken by shared reference are decoded owned, see
+/// [`borrowed_argument`].
+///
+/// # Errors
+/// - If the function takes a unit `()` argument, which decodes nothing and would be
+///   confused with the unit decoded for functions without arguments.
+/// - If the function takes an argument by reference, which `wrap_call` can't deserialize
+///   into. Owned types with references among their generic arguments are left to the
+///   compiler.
+/// - If the function returns an opaque `impl Trait` type, which `wrap_call` can't
+///   serialize.
+fn validate_signature(sig: &Signature, passed: usize, borrow_args: bool) -> syn::Result<()> {
+    let decoded = sig
+        .inputs
+        .iter()
+        .filter(|input| matches!(input, FnArg::Typed(_)))
+        .skip(passed);
+    for input in decoded {
+        if let FnArg::Typed(pat_type) = input {
+            if matches!(&*pat_type.ty, Type::Tuple(tuple) if tuple.elems.is_empty()) {
+                return Err(syn::Error::new_spanned(
+                    pat_type,
+                    format!("`{}` takes a unit `()` argument, which carries no data. Remove it, as a function without arguments is already called with `()`.", sig.ident.unraw()),
+                ));
+            }
+            if let Type::Reference(reference) = &*pat_type.ty {
+                if borrow_args && reference.mutability.is_none() {
+                    continue;
+                }
+                return Err(syn::Error::new_spanned(
+                    reference,
+                    "Exported functions can't take arguments by reference, as arguments are deserialized from the VM into owned values. Take the argument by value instead.",
+                ));
+            }
+        }
+    }
+    if let ReturnType::Type(_, ty) = &sig.output {
+        if let Some(impl_trait) = find_impl_trait(ty) {
+            return Err(syn::Error::new_spanned(
+                impl_trait,
+                "Exported functions can't return `impl Trait`, as opaque types can't be serialized across the VM boundary. Return a concrete type instead.",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the parts of a signature that the wrapper would otherwise silently ignore, with
+/// the `strict` option.
+///
+/// Arguments bound by a pattern other than an identifier, e.g. `_` or `(a, b)`, aren't
+/// decoded, and generic parameters can't be instantiated by the VM.
+///
+/// # Errors
+/// - If an argument isn't bound by an identifier.
+/// - If the method has generic parameters.
+fn reject_ignored_signature(sig: &Signature) -> syn::Result<()> {
+    let name = sig.ident.unraw();
+    if let Some(pat) = sig.inputs.iter().find_map(|input| match input {
+        FnArg::Typed(pat_type) if !matches!(&*pat_type.pat, Pat::Ident(_)) => Some(&pat_type.pat),
+        _ => None,
+    }) {
+        return Err(syn::Error::new_spanned(
+            pat,
+            format!("The arguments of the exported method `{name}` must be bound by a name, e.g. `value: u64`, as other patterns aren't decoded."),
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            format!("The exported method `{name}` can't be generic, as the VM can't choose its type arguments."),
+        ));
+    }
+    Ok(())
+}
+
+/// Ensures that a method returns a `Result`, as required by the `deny_panics` option.
+///
+/// Contracts compile to `no_std` WebAssembly, where panics can't be caught and trap the
+/// VM. A method returning a `Result` reports its errors to the caller as a value instead,
+/// which `wrap_call` serializes like any other return value.
+///
+/// # Errors
+/// If the method doesn't return a type named `Result`.
+fn require_result(sig: &Signature) -> syn::Result<()> {
+    let is_result = match &sig.output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Result"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    };
+    if is_result {
+        return Ok(());
+    }
+
+    let message = format!(
+        "With the `deny_panics` option, the method `{}` must return a `Result`, so that its errors are returned to the caller instead of aborting the call.",
+        sig.ident.unraw()
+    );
+    Err(match &sig.output {
+        ReturnType::Type(_, ty) => syn::Error::new_spanned(ty, message),
+        ReturnType::Default => syn::Error::new_spanned(sig, message),
+    })
+}
+
+/// Ensures that a function doesn't decode more arguments than `wrap_call` supports, see
+/// [`MAX_ARGUMENTS`].
+///
+/// # Errors
+/// If more than `MAX_ARGUMENTS` arguments are decoded. The error is spanned on the
+/// arguments of the function.
+fn validate_arity(sig: &Signature, arguments: usize) -> syn::Result<()> {
+    if arguments > MAX_ARGUMENTS {
+        return Err(syn::Error::new_spanned(
+            &sig.inputs,
+            format!(
+                "The function `{}` takes {arguments} arguments, but exported functions can take at most {MAX_ARGUMENTS}, as the arguments are deserialized as a tuple. Group the arguments in a struct instead.",
+                sig.ident.unraw()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Finds an `impl Trait` type in the given type, including in its generic arguments.
+fn find_impl_trait(ty: &Type) -> Option<&Type> {
+    match ty {
+        Type::ImplTrait(_) => Some(ty),
+        Type::Array(array) => find_impl_trait(&array.elem),
+        Type::Group(group) => find_impl_trait(&group.elem),
+        Type::Paren(paren) => find_impl_trait(&paren.elem),
+        Type::Reference(reference) => find_impl_trait(&reference.elem),
+        Type::Slice(slice) => find_impl_trait(&slice.elem),
+        Type::Tuple(tuple) => tuple.elems.iter().find_map(find_impl_trait),
+        Type::Path(path) => path
+            .path
+            .segments
+            .iter()
+            .filter_map(|segment| match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => Some(&arguments.args),
+                _ => None,
+            })
+            .flatten()
+            .find_map(|argument| match argument {
+                GenericArgument::Type(ty) => find_impl_trait(ty),
+                _ => None,
+            }),
+        _ => None,
+    }
+}
+
+/// Generates a `no_mangle` function decoding its arguments with `wrap_call` before running
+/// the given call block.
+///
+/// # Parameters
+/// - `name`: The name of the wrapper function.
+/// - `symbol`: The name under which the wrapper is exported, if it differs from `name`.
+/// - `body`: The body of the wrapper, decoding the arguments with [`wrap_call`].
+/// - `options`: The options passed to the `#[contract]` attribute.
+///
+/// A wrapper named by a raw identifier, e.g. `r#move` for a method named after a keyword,
+/// is exported under the name without the `r#` prefix, `move`. A wrapper whose name isn't
+/// snake case, e.g. `TraitA_value` with the `namespace_traits` option, allows the
+/// `non_snake_case` lint, as the name is dictated by the export.
+///
+/// The arguments are decoded as a tuple, which requires them to be `Sized`. The wrapper
+/// asserts it for each argument type, so that an unsized type such as `dyn Trait` or
+/// `[u8]` is reported at the type of the parameter rather than inside `wrap_call`.
+fn generate_no_mangle_wrapper(
+    name: &Ident,
+    symbol: Option<&Ident>,
+    body: TokenStream,
+    options: &ContractOptions,
+) -> TokenStream {
+    let log = call_log(symbol.unwrap_or(name), options);
+
+    // A raw identifier such as `r#move` is exported without its `r#` prefix
+    let symbol = symbol.unwrap_or(name);
+    let symbol = if symbol != name || symbol.to_string().starts_with("r#") {
+        let symbol = symbol.unraw().to_string();
+        quote! { #[export_name = #symbol] }
+    } else {
+        quote! { #[no_mangle] }
+    };
+
+    // Names such as `TraitA_value` or `totalSupply` would trigger `non_snake_case`
+    let allow_non_snake_case = name
+        .unraw()
+        .to_string()
+        .contains(|c: char| c.is_uppercase())
+        .then(|| quote! { #[allow(non_snake_case)] });
+
+    let keep = kept_export(name, quote! { unsafe fn(u32) -> u32 }, options);
+    quote! {
+        // A `no_mangle` wrapper for the `#name` function.
+        #symbol
+        #allow_non_snake_case
+        pub unsafe fn #name(arg_len: u32) -> u32 {
+            #log
+            #body
+        }
+        #keep
+    }
+}
+
+/// Generates the `#[used]` static keeping the given export alive with the `keep_exports`
+/// option, so that the linker doesn't strip it as dead code.
+///
+/// `#[used]` only applies to statics, so the wrapper is referenced by a static holding a
+/// pointer to it, e.g. `#[used] static __keep_read_value: unsafe fn(u32) -> u32 = read_value;`.
+/// The static is named after the export as is, rather than upper-cased, so that exports
+/// differing only by case, such as `TraitA_value` and `traita_value`, don't collide.
+fn kept_export(name: &Ident, ty: TokenStream, options: &ContractOptions) -> Option<TokenStream> {
+    options.keep_exports.then(|| {
+        let keep = format_ident!("__keep_{}", name.unraw());
+        quote! {
+            #[used]
+            #[allow(non_upper_case_globals)]
+            static #keep: #ty = #name;
+        }
+    })
+}
+
+/// Generates the logging of a call to the host with the `log_calls` option, recording the
+/// name under which the function is exported and the length of its arguments.
+///
+/// The call is logged through the `piecrust_debug!` macro of `dusk_core::abi`, which
+/// requires the `abi-debug` feature of `dusk-core`, and only in debug builds, so that
+/// release builds of the contract don't log.
+fn call_log(name: &Ident, options: &ContractOptions) -> Option<TokenStream> {
+    options.log_calls.then(|| {
+        let name = name.unraw().to_string();
+        quote! {
+            #[cfg(debug_assertions)]
+            {
+                dusk_core::abi::piecrust_debug!("called `{}` with {} bytes of arguments", #name, arg_len);
+            }
+        }
+    })
+}
+
+/// Generates the call to `wrap_call` decoding the arguments before running the given
+/// call block, preceded by the assertions that the argument types are `Sized`.
+///
+/// The arguments are decoded as a tuple of their types: a function without arguments
+/// decodes the unit type, `|(): ()|`, and a single argument is decoded as its own type,
+/// `|(value): (u64)|`, without being wrapped in a one-element tuple.
+///
+/// Arguments that don't decode are handled by `wrap_call`, which `dusk_core::abi`
+/// re-exports from `piecrust_uplink`: it validates the argument buffer with rkyv's
+/// `check_archived_root` before deserializing it, and panics with "Argument should
+/// correctly deserialize" if it isn't a valid archive of the argument types. The call is
+/// then aborted before the method runs, and the VM reports the failure to the caller, so
+/// the wrappers don't check the decoding themselves.
+///
+/// A diverging function, returning `!`, e.g. to always revert the call, has no return
+/// value to serialize. Its closure is declared to return `()`, which `wrap_call` can
+/// serialize but never receives, as the call aborts before returning. The closure's
+/// return type would otherwise be inferred as `!`, which `wrap_call` doesn't accept.
+fn wrap_call(
+    arg_patterns: &[Ident],
+    arg_types: &[Type],
+    call_block: TokenStream,
+    diverges: bool,
+) -> TokenStream {
+    // A closure with a return type must have a block as its body
+    let call_block = match diverges {
+        true => quote! { -> () { #call_block } },
+        false => call_block,
+    };
+    let sized_assertions = (!arg_types.is_empty()).then(|| {
+        let assertions = arg_types
+            .iter()
+            .map(|ty| quote_spanned! { ty.span()=> assert_sized::<#ty>(); });
+        quote! {
+            const _: fn() = || {
+                fn assert_sized<T: Sized>() {}
+                #(#assertions)*
+            };
+        }
+    });
+
+    quote! {
+        #sized_assertions
+        dusk_core::abi::wrap_call(arg_len, |(#(#arg_patterns),*): (#(#arg_types),*)| #call_block)
+    }
+}
+
+/// Extracts the names and types of the arguments of a function signature.
+///
+/// The receiver of instance methods is skipped, as are arguments whose pattern isn't a
+/// plain identifier.
+///
+/// # Parameters
+/// - `sig`: The signature of the function.
+///
+/// # Returns
+/// The argument names and their types, in declaration order.
+pub fn extract_arguments(sig: &Signature) -> (Vec<Ident>, Vec<Type>) {
+    sig.inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            // Extract the name from the pattern
+            FnArg::Typed(pat_type) => match &*pat_type.pat {
+                Pat::Ident(pat_ident) => Some((pat_ident.ident.clone(), (*pat_type.ty).clone())),
+                _ => None,
+            },
+            // Skip `self`
+            FnArg::Receiver(_) => None,
+        })
+        .unzip()
+}
+
+/// Extracts the return type of a function signature, replacing `Self` with the struct
+/// name so that generated code referring to it is valid outside of the `impl` block.
+///
+/// # Parameters
+/// - `sig`: The signature of the function.
+/// - `struct_name`: The name of the public struct.
+///
+/// # Returns
+/// The return type, or the unit type `()` if the function doesn't declare one.
+pub fn extract_return_type(sig: &Signature, struct_name: &Ident) -> Type {
+    let mut ty = match &sig.output {
+        ReturnType::Default => syn::parse_quote!(()),
+        ReturnType::Type(_, ty) => (**ty).clone(),
+    };
+    ReplaceSelfWithStructName { struct_name }.visit_type_mut(&mut ty);
+    ty
+}
+
+/// Checks whether a function diverges, i.e. returns the never type `!`.
+fn returns_never(sig: &Signature) -> bool {
+    matches!(&sig.output, ReturnType::Type(_, ty) if matches!(**ty, Type::Never(_)))
+}
+
+/// Replaces `Self` with the struct name in the given types.
+///
+/// # Parameters
+/// - `types`: The types to rewrite.
+/// - `struct_name`: The name of the public struct.
+pub fn replace_self_in_types(types: &mut [Type], struct_name: &Ident) {
+    let mut visitor = ReplaceSelfWithStructName { struct_name };
+    for ty in types {
+        visitor.visit_type_mut(ty);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_free_functions, generate_public_functions, ExportContext};
+    use crate::contract::options::ContractOptions;
+    use crate::contract::selector::SelectorScheme;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use proc_macro2::TokenStream;
+    use quote::quote;
+    use syn::{parse_quote, Ident, ItemFn, ItemImpl};
+
+    /// Generates the wrappers of the given `impl` block of a `Counter` contract declared
+    /// in the `counter` module.
+    fn generate(imp: ItemImpl, options: &ContractOptions) -> syn::Result<Vec<TokenStream>> {
+        generate_blocks(&[imp], options)
+    }
+
+    fn generate_blocks(
+        impl_blocks: &[ItemImpl],
+        options: &ContractOptions,
+    ) -> syn::Result<Vec<TokenStream>> {
+        let struct_name: Ident = parse_quote!(Counter);
+        let cx = ExportContext {
+            struct_name: &struct_name,
+            state: quote!(STATE),
+            options,
+            symbols: &[],
+        };
+        generate_public_functions(impl_blocks, &cx)
+    }
+
+    #[test]
+    fn test_restricted_visibility_methods_are_not_exported() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub(crate) fn crate_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub(super) fn super_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub(in crate::counter) fn path_value(&self) -> i64 {
+                    self.value
+                }
+
+                fn private_value(&self) -> i64 {
+                    self.value
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        assert_eq!(functions.len(), 1);
+        assert!(functions[0].to_string().contains("fn read_value"));
+    }
+
+    #[test]
+    fn test_wrappers_follow_declaration_order() {
+        let blocks: [ItemImpl; 3] = [
+            parse_quote! {
+                impl Counter {
+                    pub fn zeta(&self) {}
+
+                    pub fn alpha(&self) {}
+                }
+            },
+            parse_quote! {
+                impl Reset for Counter {
+                    fn reset(&mut self) {}
+                }
+            },
+            parse_quote! {
+                impl Counter {
+                    pub fn beta(&self) {}
+                }
+            },
+        ];
+        let functions = generate_blocks(&blocks, &ContractOptions::default()).unwrap();
+
+        let names: Vec<_> = functions
+            .iter()
+            .map(|function| {
+                let function: ItemFn = syn::parse2(function.clone()).unwrap();
+                function.sig.ident.to_string()
+            })
+            .collect();
+        assert_eq!(names, ["zeta", "alpha", "reset", "beta"]);
+    }
+
+    #[test]
+    fn test_free_function_wrapper_calls_module_function() {
+        let func: ItemFn = parse_quote! {
+            pub fn hash(data: Vec<u8>) -> [u8; 32] {
+                [0; 32]
+            }
+        };
+        let functions = generate_free_functions(&[func], &ContractOptions::default()).unwrap();
+
+        assert_eq!(functions.len(), 1);
+        let wrapper = functions[0].to_string();
+        assert!(wrapper.contains(
+            &quote! { #[export_name = "hash"] pub unsafe fn __export_hash(arg_len: u32) -> u32 }
+                .to_string()
+        ));
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(data): (Vec<u8>)| hash(data))
+        };
+        assert!(wrapper.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_zero_argument_methods_decode_unit() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn get(&self) -> u64 {
+                    self.value
+                }
+
+                pub fn zero() -> u64 {
+                    0
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let getter = functions[0].to_string();
+        let expected = quote! {
+            pub unsafe fn get(arg_len: u32) -> u32 {
+                dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.get())
+            }
+        };
+        assert!(getter.contains(&expected.to_string()));
+
+        let static_method = functions[1].to_string();
+        let expected = quote! {
+            pub unsafe fn zero(arg_len: u32) -> u32 {
+                dusk_core::abi::wrap_call(arg_len, |(): ()| Counter::zero())
+            }
+        };
+        assert!(static_method.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_tuple_returns_are_passed_through() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn pair(&self) -> (u64, bool) {
+                    (self.value, true)
+                }
+
+                pub fn single(&self, value: u64) -> (u64,) {
+                    (value,)
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let pair = functions[0].to_string();
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.pair())
+        };
+        assert!(pair.contains(&expected.to_string()));
+
+        // The return type isn't affected by the single argument decoded as its own type
+        let single = functions[1].to_string();
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(value): (u64)| STATE.single(value))
+        };
+        assert!(single.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_option_returns_are_passed_through() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn get(&self, key: u64) -> Option<u64> {
+                    self.values.get(&key).copied()
+                }
+            }
+        };
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(key): (u64)| STATE.get(key))
+        };
+
+        let functions = generate(imp.clone(), &ContractOptions::default()).unwrap();
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+
+        // The returned reference check of `auto_clone_returns` doesn't apply to `Option`
+        let options = ContractOptions {
+            auto_clone_returns: true,
+            ..ContractOptions::default()
+        };
+        let functions = generate(imp.clone(), &options).unwrap();
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+
+        // An `Option` isn't mistaken for a `Result`
+        let options = ContractOptions {
+            deny_panics: true,
+            ..ContractOptions::default()
+        };
+        let error = generate(imp, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("the method `get` must return a `Result`"));
+    }
+
+    #[test]
+    fn test_generic_arguments_of_self_type_are_kept() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter<u32> {
+                pub fn zero() -> u32 {
+                    0
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| Counter::<u32>::zero())
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_array_arguments_are_decoded() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn set_hash(&mut self, hash: [u8; 32]) {}
+
+                pub fn verify(&self, key: [u8; 32], signature: [u8; 64]) -> bool {
+                    true
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let single = functions[0].to_string();
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(hash): ([u8; 32])| STATE.set_hash(hash))
+        };
+        assert!(single.contains(&expected.to_string()));
+
+        let pair = functions[1].to_string();
+        let expected = quote! {
+            dusk_core::abi::wrap_call(
+                arg_len,
+                |(key, signature): ([u8; 32], [u8; 64])| STATE.verify(key, signature)
+            )
+        };
+        assert!(pair.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_self_qualified_argument_types_are_rewritten() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn lookup(&self, key: Self::Key) -> u64 {
+                    self.values[&key]
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrapper = functions[0].to_string();
+        assert!(wrapper.contains(&quote! { |(key): (Counter::Key)| }.to_string()));
+        assert!(!wrapper.contains("Self"));
+    }
+
+    #[test]
+    fn test_qualified_associated_argument_types_are_kept() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn credit(&mut self, amount: <Self as Token>::Balance) {}
+
+                pub fn debit(&mut self, amount: <Counter as Token>::Balance) {}
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! { |(amount): (<Counter as Token>::Balance)| };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+        assert!(functions[1].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_diverging_method_returns_unit_to_wrap_call() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn revert(&self, code: u32) -> ! {
+                    panic!("reverted with {code}")
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(code): (u32)| -> () { STATE.revert(code) })
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.read_value())
+        };
+        assert!(functions[1].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_self_typed_argument_is_rewritten() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn merge(&mut self, other: Self) {
+                    self.value += other.value;
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrapper = functions[0].to_string();
+        assert!(wrapper.contains(&quote! { |(other): (Counter)| STATE.merge(other) }.to_string()));
+        assert!(!wrapper.contains("Self"));
+    }
+
+    #[test]
+    fn test_self_nested_in_argument_type_is_rewritten() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn merge_all(&mut self, others: Vec<Self>, fallback: Option<Self>) {}
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrapper = functions[0].to_string();
+        let expected = quote! { |(others, fallback): (Vec<Counter>, Option<Counter>)| };
+        assert!(wrapper.contains(&expected.to_string()));
+        assert!(!wrapper.contains("Self"));
+    }
+
+    #[test]
+    fn test_argument_types_are_asserted_sized() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn hash(&self, hasher: dyn Hasher, data: Vec<u8>) {}
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrapper = functions[0].to_string();
+        assert!(wrapper.contains(&quote! { fn assert_sized<T: Sized>() {} }.to_string()));
+        assert!(wrapper.contains(&quote! { assert_sized::<dyn Hasher>(); }.to_string()));
+        assert!(wrapper.contains(&quote! { assert_sized::<Vec<u8> >(); }.to_string()));
+        // Wrappers without arguments have nothing to assert
+        assert!(!functions[1].to_string().contains("assert_sized"));
+    }
+
+    #[test]
+    fn test_raw_identifier_is_exported_unraw() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn r#move(&mut self, to: u64) {
+                    self.position = to;
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrapper = functions[0].to_string();
+        assert!(wrapper.contains(
+            &quote! { #[export_name = "move"] pub unsafe fn r#move(arg_len: u32) -> u32 }
+                .to_string()
+        ));
+        assert!(wrapper.contains(&quote! { STATE.r#move(to) }.to_string()));
+        assert!(!wrapper.contains("no_mangle"));
+    }
+
+    #[test]
+    fn test_impl_trait_return_is_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn values(&self) -> impl Iterator<Item = u32> {
+                    self.values.iter().copied()
+                }
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("Exported functions can't return `impl Trait`"));
+    }
+
+    #[test]
+    fn test_nested_impl_trait_return_is_rejected() {
+        let func: ItemFn = parse_quote! {
+            pub fn values() -> Option<impl Iterator<Item = u32>> {
+                None::<core::iter::Empty<u32>>
+            }
+        };
+        assert!(generate_free_functions(&[func], &ContractOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_reference_argument_is_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add(&mut self, value: &u32) {
+                    self.value += *value;
+                }
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("Exported functions can't take arguments by reference"));
+    }
+
+    #[test]
+    fn test_borrowed_argument_is_decoded_owned() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn process(&mut self, req: &Request, times: u32) {
+                    self.value += req.amount * times as u64;
+                }
+            }
+        };
+        let options = ContractOptions {
+            borrow_args: true,
+            ..Default::default()
+        };
+        let functions = generate(imp.clone(), &options).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(req, times): (Request, u32)| STATE.process(&req, times))
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn process(&mut self, req: &mut Request) {}
+            }
+        };
+        let error = generate(imp, &options).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Exported functions can't take arguments by reference"));
+    }
+
+    #[test]
+    fn test_default_argument_is_decoded_as_option() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add(&mut self, value: u64, #[default(0)] bonus: u64) {
+                    self.value += value + bonus;
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(value, bonus): (u64, Option<u64>)| {
+                let bonus = bonus.unwrap_or_else(|| 0);
+                STATE.add(value, bonus)
+            })
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add(&mut self, #[default] bonus: u64) {}
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`#[default]` must give the default value of the argument, e.g. `#[default(0)]`."
+        );
+    }
+
+    #[test]
+    fn test_unit_argument_is_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn ping(&self, _: ()) {}
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`ping` takes a unit `()` argument, which carries no data. Remove it, as a function without arguments is already called with `()`."
+        );
+    }
+
+    #[test]
+    fn test_export_aliases_call_the_same_method() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[export(name = "transfer_from", alias = "transferFrom")]
+                pub fn transfer(&mut self, to: u64) {}
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let wrappers = functions[0].to_string();
+        for name in ["transfer_from", "transferFrom"] {
+            let name = Ident::new(name, proc_macro2::Span::call_site());
+            let signature = quote! { pub unsafe fn #name(arg_len: u32) -> u32 };
+            assert!(wrappers.contains(&signature.to_string()));
+        }
+        let call = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(to): (u64)| STATE.transfer(to))
+        };
+        assert_eq!(wrappers.matches(&call.to_string()).count(), 2);
+        assert!(!wrappers.contains("fn transfer ("));
+    }
+
+    #[test]
+    fn test_export_alias_collision_is_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn total(&self) -> u64 {
+                    self.value
+                }
+
+                #[export(alias = "total")]
+                pub fn sum(&self) -> u64 {
+                    self.value
+                }
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("The method `total` is exported more than once"));
+
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[export(rename = "total")]
+                pub fn sum(&self) -> u64 {
+                    self.value
+                }
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unsupported `#[export]` argument, expected `name` or `alias`"
+        );
+    }
+
+    #[test]
+    fn test_keep_exports_references_wrappers_from_used_statics() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> u64 {
+                    self.value
+                }
+            }
+        };
+        let options = ContractOptions {
+            keep_exports: true,
+            ..Default::default()
+        };
+        let functions = generate(imp.clone(), &options).unwrap();
+
+        let expected = quote! {
+            #[used]
+            #[allow(non_upper_case_globals)]
+            static __keep_read_value: unsafe fn(u32) -> u32 = read_value;
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+        assert!(!functions[0].to_string().contains("used"));
+    }
+
+    #[test]
+    fn test_keep_exports_statics_differing_by_case_do_not_collide() {
+        let options = ContractOptions {
+            keep_exports: true,
+            namespace_traits: true,
+            ..Default::default()
+        };
+        let inherent = generate(
+            parse_quote! {
+                impl Counter {
+                    pub fn traita_value(&self) -> u64 {
+                        self.value
+                    }
+                }
+            },
+            &options,
+        )
+        .unwrap();
+        let trait_impl = generate(
+            parse_quote! {
+                impl TraitA for Counter {
+                    fn value(&self) -> u64 {
+                        self.value
+                    }
+                }
+            },
+            &options,
+        )
+        .unwrap();
+
+        assert!(inherent[0]
+            .to_string()
+            .contains(&quote! { static __keep_traita_value }.to_string()));
+        assert!(trait_impl[0]
+            .to_string()
+            .contains(&quote! { static __keep_TraitA_value }.to_string()));
+    }
+
+    #[test]
+    fn test_too_many_arguments_are_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn set_all(
+                    &mut self,
+                    a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8,
+                    h: u8, i: u8, j: u8, k: u8, l: u8, m: u8,
+                ) {}
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "The function `set_all` takes 13 arguments, but exported functions can take at most 12, as the arguments are deserialized as a tuple. Group the arguments in a struct instead."
+        );
+    }
+
+    #[test]
+    fn test_maximum_arguments_are_accepted() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn set_all(
+                    &mut self,
+                    a: u8, b: u8, c: u8, d: u8, e: u8, f: u8,
+                    g: u8, h: u8, i: u8, j: u8, k: u8, l: u8,
+                ) {}
+            }
+        };
+
+        assert!(generate(imp, &ContractOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_owned_generic_arguments_are_accepted() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn set_data(&mut self, data: Vec<u8>) {}
+
+                pub fn set_name(&mut self, name: Option<String>) {}
+
+                pub fn set_entries(&mut self, entries: Vec<Option<(u64, [u8; 32])>>) {}
+            }
+        };
+        let wrappers = generate(imp, &ContractOptions::default()).unwrap();
+
+        assert_eq!(wrappers.len(), 3);
+        assert!(wrappers[0]
+            .to_string()
+            .contains(&quote!((data): (Vec<u8>)).to_string()));
+        assert!(wrappers[1]
+            .to_string()
+            .contains(&quote!((name): (Option<String>)).to_string()));
+        // The closing angle brackets are separate tokens in the parsed type
+        assert!(wrappers[2]
+            .to_string()
+            .contains(&quote!((entries): (Vec<Option<(u64, [u8; 32])> >)).to_string()));
+    }
+
+    fn conflicting_trait_impls() -> [ItemImpl; 2] {
+        [
+            parse_quote! {
+                impl TraitA for Counter {
+                    fn value(&self) -> u64 {
+                        self.a
+                    }
+                }
+            },
+            parse_quote! {
+                impl traits::TraitB for Counter {
+                    fn value(&mut self) -> u64 {
+                        self.b
+                    }
+                }
+            },
+        ]
+    }
+
+    #[test]
+    fn test_conflicting_trait_methods_are_rejected() {
+        let error =
+            generate_blocks(&conflicting_trait_impls(), &ContractOptions::default()).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("The method `value` is exported more than once"));
+    }
+
+    #[test]
+    fn test_namespace_traits_prefixes_trait_methods() {
+        let options = ContractOptions {
+            namespace_traits: true,
+            ..ContractOptions::default()
+        };
+        let wrappers = generate_blocks(&conflicting_trait_impls(), &options).unwrap();
+
+        assert_eq!(wrappers.len(), 2);
+        let first = wrappers[0].to_string();
+        assert!(
+            first.contains(&quote! { pub unsafe fn TraitA_value(arg_len: u32) -> u32 }.to_string())
+        );
+        assert!(first.contains(&quote! { <Counter as TraitA>::value(&STATE) }.to_string()));
+        let second = wrappers[1].to_string();
+        assert!(second
+            .contains(&quote! { pub unsafe fn TraitB_value(arg_len: u32) -> u32 }.to_string()));
+        assert!(
+            second.contains(&quote! { <Counter as traits::TraitB>::value(&mut STATE) }.to_string())
+        );
+    }
+
+    #[test]
+    fn test_non_snake_case_exports_allow_the_lint() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn totalSupply(&self) -> u64 {
+                    self.supply
+                }
+
+                pub fn read_value(&self) -> u64 {
+                    self.value
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            #[no_mangle]
+            #[allow(non_snake_case)]
+            pub unsafe fn totalSupply(arg_len: u32) -> u32
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+        assert!(!functions[1].to_string().contains("non_snake_case"));
+    }
+
+    #[test]
+    fn test_single_trait_method_keeps_its_name() {
+        let [imp, _] = conflicting_trait_impls();
+        let wrappers = generate(imp, &ContractOptions::default()).unwrap();
+
+        assert!(wrappers[0]
+            .to_string()
+            .contains(&quote! { pub unsafe fn value(arg_len: u32) -> u32 }.to_string()));
+    }
+
+    #[test]
+    fn test_only_owner_method_is_guarded() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[only_owner]
+                pub fn reset(&mut self) {
+                    self.value = 0;
+                }
+
+                pub fn increment(&mut self) {
+                    self.value += 1;
+                }
+            }
+        };
+        let options = ContractOptions {
+            owner_field: Some(parse_quote!(owner)),
+            ..ContractOptions::default()
+        };
+
+        let functions = generate(imp, &options).unwrap();
+
+        let guard = quote! {
+            assert!(
+                dusk_core::abi::caller() == Some(STATE.owner),
+                "only the owner can call `reset`"
+            );
+        };
+        assert!(functions[0].to_string().contains(&guard.to_string()));
+        assert!(!functions[1].to_string().contains("caller"));
+    }
+
+    #[test]
+    fn test_payable_method_claims_deposit() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[payable]
+                pub fn stake(&mut self, value: u64) {
+                    self.staked += value;
+                }
+
+                pub fn read_value(&self) -> u64 {
+                    self.staked
+                }
+            }
+        };
+
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let claim = quote! {
+            dusk_core::abi::call::<_, ()>(dusk_core::transfer::TRANSFER_CONTRACT, "deposit", &value)
+                .expect("claiming the deposit of the transaction must succeed");
+            STATE.stake(value)
+        };
+        assert!(functions[0].to_string().contains(&claim.to_string()));
+
+        // Methods that aren't payable leave the deposit to be refunded
+        assert!(!functions[1].to_string().contains("deposit"));
+    }
+
+    #[test]
+    fn test_deposit_query_guards_methods_that_are_not_payable() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[payable]
+                pub fn stake(&mut self, value: u64) {
+                    self.staked += value;
+                }
+
+                pub fn read_value(&self) -> u64 {
+                    self.staked
+                }
+            }
+        };
+        let options = ContractOptions {
+            deposit_query: Some(parse_quote!("transaction_deposit")),
+            ..ContractOptions::default()
+        };
+
+        let functions = generate(imp, &options).unwrap();
+
+        let guard = quote! {
+            pub unsafe fn read_value(arg_len: u32) -> u32 {
+                if dusk_core::abi::caller() == Some(dusk_core::transfer::TRANSFER_CONTRACT) {
+                    let deposit = dusk_core::abi::call::<_, u64>(dusk_core::transfer::TRANSFER_CONTRACT, "transaction_deposit", &())
+                        .expect("querying the deposit of the transaction must succeed");
+                    assert!(deposit == 0, "the method isn't payable, so the transaction must not deposit a value");
+                }
+                dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.read_value())
+            }
+        };
+        assert!(functions[1].to_string().contains(&guard.to_string()));
+        assert!(!functions[0].to_string().contains("transaction_deposit"));
+    }
+
+    #[test]
+    fn test_payable_method_requires_value_argument() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[payable]
+                pub fn stake(&mut self, receiver: ContractId) {}
+            }
+        };
+
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("`#[payable]` methods must take the transferred value"));
+    }
+
+    #[test]
+    fn test_auto_clone_returns_clones_borrowed_slice() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn data(&self) -> &[u8] {
+                    &self.data
+                }
+            }
+        };
+        let options = ContractOptions {
+            auto_clone_returns: true,
+            ..Default::default()
+        };
+
+        let functions = generate(imp, &options).unwrap();
+
+        assert!(functions[0]
+            .to_string()
+            .contains(&quote! { alloc::borrow::ToOwned::to_owned(STATE.data()) }.to_string()));
+    }
+
+    #[test]
+    fn test_auto_clone_returns_rejects_other_references() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn value(&self) -> &u64 {
+                    &self.value
+                }
+            }
+        };
+        let options = ContractOptions {
+            auto_clone_returns: true,
+            ..Default::default()
+        };
+
+        let error = generate(imp, &options).err().unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("`auto_clone_returns` only supports methods returning `&str` or `&[T]`"));
+    }
+
+    #[test]
+    fn test_entry_dispatches_to_methods() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn add(&mut self, value: i64) {
+                    self.value += value;
+                }
+            }
+        };
+        let options = ContractOptions {
+            entry: Some(parse_quote!(call)),
+            ..Default::default()
+        };
+
+        let functions = generate(imp, &options).unwrap();
+
+        assert_eq!(functions.len(), 1);
+        let dispatch = functions[0].to_string();
+        assert!(dispatch.contains(&quote! { pub unsafe fn call(arg_len: u32) -> u32 }.to_string()));
+        let decode = quote! {
+            let selector = core::cell::Cell::new(0u32);
+            dusk_core::abi::wrap_call(4, |bytes: [u8; 4]| selector.set(u32::from_le_bytes(bytes)));
+        };
+        assert!(dispatch.contains(&decode.to_string()));
+        assert!(dispatch.contains(
+            &quote! { 0u32 => { dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.read_value()) } }
+                .to_string()
+        ));
+        assert!(dispatch.contains(&quote! { 1u32 => }.to_string()));
+        assert!(dispatch.contains(&quote! { STATE.add(value) }.to_string()));
+        assert!(dispatch.contains(&quote! { _ => panic!("unknown method selector"), }.to_string()));
+        assert!(!dispatch.contains("fn read_value"));
+    }
+
+    #[test]
+    fn test_test_exports_call_methods_natively() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add(&mut self, value: i64) -> i64 {
+                    self.value += value;
+                    self.value
+                }
+
+                pub fn name(&self) -> &str {
+                    "counter"
+                }
+            }
+        };
+        let options = ContractOptions {
+            test_exports: true,
+            auto_clone_returns: true,
+            ..Default::default()
+        };
+
+        let functions = generate(imp, &options).unwrap();
+
+        let add = functions[0].to_string();
+        assert!(add.contains(&quote! { pub unsafe fn add(arg_len: u32) -> u32 }.to_string()));
+        let expected = quote! {
+            pub unsafe fn __call_add(value: i64) -> i64 {
+                STATE.add(value)
+            }
+        };
+        assert!(add.contains(&expected.to_string()));
+        assert!(add.contains("# Safety"));
+        let expected = quote! { pub unsafe fn __call_name() -> alloc::string::String };
+        assert!(functions[1].to_string().contains(&expected.to_string()));
+
+        let dispatch = generate(
+            parse_quote! {
+                impl Counter {
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+            },
+            &ContractOptions {
+                test_exports: true,
+                entry: Some(parse_quote!(call)),
+                ..Default::default()
+            },
+        )
+        .unwrap()[0]
+            .to_string();
+        assert!(dispatch.contains(&quote! { pub unsafe fn __call_read_value() -> i64 }.to_string()));
+    }
+
+    #[test]
+    fn test_keccak_selectors_hash_export_names() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn add(&mut self, value: i64) {
+                    self.value += value;
+                }
+            }
+        };
+        let options = ContractOptions {
+            entry: Some(parse_quote!(call)),
+            selector: Some(SelectorScheme::Keccak),
+            ..Default::default()
+        };
+
+        let dispatch = generate(imp, &options).unwrap()[0].to_string();
+
+        for (index, name) in ["read_value", "add"].into_iter().enumerate() {
+            let selector = SelectorScheme::Keccak.selector(index, name);
+            assert!(dispatch.contains(&quote! { #selector => }.to_string()));
+        }
+        // The index selectors aren't dispatched
+        assert!(!dispatch.contains("match selector . get () { 0u32 =>"));
+        assert!(!dispatch.contains("} 1u32 =>"));
+    }
+
+    #[test]
+    fn test_validate_method_checks_arguments() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[validate(check_transfer)]
+                pub fn transfer(&mut self, to: u64, amount: u64) {}
+
+                fn check_transfer(to: &u64, amount: &u64) -> Result<(), &'static str> {
+                    Ok(())
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        assert_eq!(functions.len(), 1);
+        let expected = quote! {
+            {
+                Counter::check_transfer(&to, &amount).expect("invalid arguments for `transfer`");
+                STATE.transfer(to, amount)
+            }
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_deny_panics_requires_result_returns() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn increment(&mut self) {
+                    self.value += 1;
+                }
+            }
+        };
+        let options = ContractOptions {
+            deny_panics: true,
+            ..ContractOptions::default()
+        };
+        let error = generate(imp, &options).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "With the `deny_panics` option, the method `increment` must return a `Result`, so that its errors are returned to the caller instead of aborting the call."
+        );
+    }
+
+    #[test]
+    fn test_deny_panics_returns_validation_errors() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[validate(check_transfer)]
+                pub fn transfer(&mut self, to: u64, amount: u64) -> Result<(), Error> {
+                    Ok(())
+                }
+
+                fn check_transfer(to: &u64, amount: &u64) -> Result<(), Error> {
+                    Ok(())
+                }
+            }
+        };
+        let options = ContractOptions {
+            deny_panics: true,
+            ..ContractOptions::default()
+        };
+        let functions = generate(imp, &options).unwrap();
+
+        let expected = quote! {
+            {
+                Counter::check_transfer(&to, &amount)?;
+                STATE.transfer(to, amount)
+            }
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_strict_rejects_ignored_arguments() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add(&mut self, (a, b): (u64, u64)) {}
+            }
+        };
+        assert!(generate(imp.clone(), &ContractOptions::default()).is_ok());
+
+        let options = ContractOptions {
+            strict: true,
+            ..ContractOptions::default()
+        };
+        let error = generate(imp, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The arguments of the exported method `add` must be bound by a name, e.g. `value: u64`, as other patterns aren't decoded."
+        );
+    }
+
+    #[test]
+    fn test_strict_rejects_generic_methods() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn add<T: Into<u64>>(&mut self, value: T) {}
+            }
+        };
+        assert!(generate(imp.clone(), &ContractOptions::default()).is_ok());
+
+        let options = ContractOptions {
+            strict: true,
+            ..ContractOptions::default()
+        };
+        let error = generate(imp, &options).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The exported method `add` can't be generic, as the VM can't choose its type arguments."
+        );
+    }
+
+    #[test]
+    fn test_log_calls_logs_each_wrapper() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn increment(&mut self) {}
+
+                pub fn add(&mut self, value: u64) {}
+            }
+        };
+        let options = ContractOptions {
+            log_calls: true,
+            ..ContractOptions::default()
+        };
+        let functions = generate(imp.clone(), &options).unwrap();
+
+        for (function, name) in functions.iter().zip(["increment", "add"]) {
+            let function = function.to_string();
+            let log = quote! {
+                #[cfg(debug_assertions)]
+                {
+                    dusk_core::abi::piecrust_debug!("called `{}` with {} bytes of arguments", #name, arg_len);
+                }
+            }
+            .to_string();
+            // The call is logged before the arguments are decoded
+            let call = quote!(dusk_core::abi::wrap_call).to_string();
+            assert!(function.find(&log).unwrap() < function.find(&call).unwrap());
+        }
+
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+        assert!(functions
+            .iter()
+            .all(|function| !function.to_string().contains("piecrust_debug")));
+    }
+
+    #[test]
+    fn test_pre_hook_runs_before_every_method() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn increment(&mut self) {}
+
+                pub fn zero() -> u64 {
+                    0
+                }
+
+                fn check_not_paused(&self) -> Result<(), &'static str> {
+                    Ok(())
+                }
+            }
+        };
+        let options = ContractOptions {
+            pre: Some(parse_quote!(check_not_paused)),
+            ..ContractOptions::default()
+        };
+        let functions = generate(imp, &options).unwrap();
+
+        assert_eq!(functions.len(), 2);
+        let increment = quote! {
+            {
+                Counter::check_not_paused(&STATE).expect("the `pre` hook `check_not_paused` rejected the call to `increment`");
+                STATE.increment()
+            }
+        };
+        assert!(functions[0].to_string().contains(&increment.to_string()));
+        let zero = quote! {
+            {
+                Counter::check_not_paused(&STATE).expect("the `pre` hook `check_not_paused` rejected the call to `zero`");
+                Counter::zero()
+            }
+        };
+        assert!(functions[1].to_string().contains(&zero.to_string()));
+    }
+
+    #[test]
+    fn test_uses_state_passes_state_to_associated_function() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[uses_state]
+                pub fn total(state: &Self, extra: u64) -> u64 {
+                    state.value + extra
+                }
+
+                #[uses_state]
+                pub fn reset(state: &mut Counter) {
+                    state.value = 0;
+                }
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let total = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(extra): (u64)| {
+                let state = &STATE;
+                Counter::total(state, extra)
+            })
+        };
+        assert!(functions[0].to_string().contains(&total.to_string()));
+        let reset = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| {
+                let state = &mut STATE;
+                Counter::reset(state)
+            })
+        };
+        assert!(functions[1].to_string().contains(&reset.to_string()));
+    }
+
+    #[test]
+    fn test_uses_state_requires_state_reference() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[uses_state]
+                pub fn total(extra: u64) -> u64 {
+                    extra
+                }
+            }
+        };
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`#[uses_state]` requires the first argument of `total` to be a reference to the state, e.g. `state: &Self`."
+        );
+    }
+
+    #[test]
+    fn test_validate_requires_validator() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[validate]
+                pub fn transfer(&mut self, to: u64, amount: u64) {}
+            }
+        };
+
+        let error = generate(imp, &ContractOptions::default()).err().unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("`#[validate]` must name the function validating the arguments"));
+    }
+
+    #[test]
+    fn test_caller_argument_is_injected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn vote(&mut self, #[caller] caller: Option<ContractId>, choice: u8) {}
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(choice): (u8)| {
+                let caller = dusk_core::abi::caller();
+                STATE.vote(caller, choice)
+            })
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_caller_argument_follows_state_argument() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[uses_state]
+                pub fn who(state: &Self, #[caller] caller: Option<ContractId>, x: u8) {}
+            }
+        };
+        let functions = generate(imp, &ContractOptions::default()).unwrap();
+
+        let expected = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(x): (u8)| {
+                let state = &STATE;
+                let caller = dusk_core::abi::caller();
+                Counter::who(state, caller, x)
+            })
+        };
+        assert!(functions[0].to_string().contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_misplaced_caller_is_rejected() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn vote(&mut self, choice: u8, #[caller] caller: Option<ContractId>) {}
+            }
+        };
+
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "`#[caller]` must be on the first argument decoded from the call of `vote`, after `self` or the state passed by `#[uses_state]`."
+        );
+    }
+
+    #[test]
+    fn test_caller_arg_injects_argument_named_caller() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                pub fn vote(&mut self, caller: Option<ContractId>) {}
+            }
+        };
+
+        let decoded = generate(imp.clone(), &ContractOptions::default()).unwrap();
+        assert!(decoded[0]
+            .to_string()
+            .contains(&quote! { |(caller): (Option<ContractId>)| }.to_string()));
+
+        let options = ContractOptions {
+            caller_arg: true,
+            ..Default::default()
+        };
+        let injected = generate(imp, &options).unwrap();
+        assert!(injected[0]
+            .to_string()
+            .contains(&quote! { |(): ()| }.to_string()));
+    }
+
+    #[test]
+    fn test_only_owner_requires_owner_field() {
+        let imp: ItemImpl = parse_quote! {
+            impl Counter {
+                #[only_owner]
+                pub fn reset(&mut self) {
+                    self.value = 0;
+                }
+            }
+        };
+
+        let error = generate(imp, &ContractOptions::default()).unwrap_err();
+
+        assert!(error.to_string().contains("`#[only_owner]` requires"));
     }
 }
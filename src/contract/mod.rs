@@ -1,53 +1,1610 @@
+mod abi;
+mod caller;
 mod functions;
+mod options;
 mod parser;
+mod selector;
 mod state;
 mod transformation;
 
-use functions::generate_public_functions;
-use parser::parse_contract;
-use state::generate_state_declaration;
+use abi::{generate_abi_function, generate_abi_json, generate_name_function};
+use caller::generate_caller;
+use functions::{
+    generate_deploy_args, generate_free_functions, generate_init_function,
+    generate_public_functions, ExportContext,
+};
+use options::ContractOptions;
+use parser::{parse_contract, parse_contract_impl, Contract};
+use state::{generate_snapshot_function, generate_state_declaration, state_access};
 
+use alloc::string::ToString;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, ItemMod};
+use quote::{quote, ToTokens};
+use syn::{File, Ident, Item, ItemImpl, ItemMod};
 
 /// Expands the `#[dusk_forge::contract]` macro.
 ///
 /// This macro simplifies smart contract development by:
 /// 1. Parsing the annotated module to identify public structs, impl blocks,
 ///    and trait implementations.
-/// 2. Generating `no_mangle` functions for all public methods in `impl` blocks.
-/// 3. Automatically defining a `static mut STATE` for the module's public struct.
+/// 2. Generating `no_mangle` functions for all public methods in `impl` blocks, emitted
+///    in the module. The symbols are exported regardless of the module's visibility.
+/// 3. Automatically defining a `static mut STATE` for the module's public struct, and an
+///    `init` export when the state must be initialized at runtime.
 /// 4. Validating that the module contains exactly one public struct.
 ///
+/// The macro can also be applied to a single `impl` block, for contracts declaring their
+/// own `static mut STATE`.
+///
+/// The behavior can be adjusted with options passed to the attribute, see
+/// [`ContractOptions`].
+///
 /// # Errors
 /// If the module contains:
-/// - No public struct.
+/// - No public struct, unless the `no_state` option is set.
 /// - Multiple public structs.
+/// - No exported method, when the `require_methods` option is set.
+///
 /// The macro will return a compile-time error.
-pub fn expand_contract(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    let mut input_mod = parse_macro_input!(item as ItemMod);
+pub fn expand_contract(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr.into(), item.into())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Performs the expansion of the `#[contract]` macro on `proc_macro2` tokens,
+/// so it can be exercised outside of a procedural macro invocation.
+///
+/// The macro is applied either to a contract module, or to an `impl` block of a type
+/// whose state is declared by the user, see [`expand_impl`].
+fn expand(
+    attr: proc_macro2::TokenStream,
+    item: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let options = ContractOptions::parse(attr)?;
+    match syn::parse2(item)? {
+        Item::Mod(input_mod) => expand_module(options, input_mod),
+        Item::Impl(input_impl) => expand_impl(options, input_impl),
+        item => Err(syn::Error::new_spanned(
+            item,
+            "`#[contract]` must be applied to a module, or to an `impl` block of the contract's state.",
+        )),
+    }
+}
+
+/// Expands a contract module, declaring its state and generating the wrappers of the
+/// methods of its public struct.
+fn expand_module(
+    options: ContractOptions,
+    mut input_mod: ItemMod,
+) -> syn::Result<proc_macro2::TokenStream> {
     let mod_name = input_mod.ident.clone();
 
     // Parse contract components
-    let (struct_name, impl_blocks, new_function_body) = match parse_contract(&mut input_mod) {
-        Ok(data) => data,
-        Err(err) => return err,
+    let Contract {
+        struct_name,
+        generics,
+        visibility,
+        impl_blocks,
+        constructor,
+        functions,
+        symbols,
+    } = parse_contract(&mut input_mod, &options)?;
+
+    // A stateless contract without a struct is named after its module
+    let struct_name = struct_name.unwrap_or_else(|| mod_name.clone());
+
+    // Generate the state for the contract, unless it's stateless
+    let constructor = match options.no_state {
+        true => None,
+        false => {
+            generate_state_declaration(
+                &struct_name,
+                &generics,
+                &visibility,
+                constructor.as_ref(),
+                &options,
+                &mut input_mod,
+            )?;
+            // `generate_state_declaration` ensures the constructor exists
+            constructor
+        }
+    };
+    let cx = ExportContext {
+        struct_name: &struct_name,
+        // The methods of a stateless contract never access the state
+        state: constructor.as_ref().map_or_else(
+            || quote! { STATE },
+            |constructor| state_access(&struct_name, constructor, &options),
+        ),
+        options: &options,
+        symbols: &symbols,
     };
 
-    // Generate the state for the contract
-    if let Err(err) = generate_state_declaration(&struct_name, new_function_body, &mut input_mod) {
-        return err;
-    }
+    // Generate the `init` function for state initialized at runtime
+    let init_function = constructor
+        .as_ref()
+        .and_then(|constructor| generate_init_function(constructor, &cx));
+    let deploy_args = constructor
+        .as_ref()
+        .filter(|_| options.client)
+        .and_then(|constructor| generate_deploy_args(constructor, &cx));
 
     // Generate `no_mangle` functions for public methods
-    let generated_functions = generate_public_functions(&impl_blocks, &mod_name);
+    let mut generated_functions = generate_public_functions(&impl_blocks, &cx)?;
+    if options.export_fns {
+        generated_functions.extend(generate_free_functions(&functions, &options)?);
+    }
+    let snapshot = options
+        .snapshot
+        .then(|| generate_snapshot_function(&struct_name, &cx.state, &input_mod))
+        .transpose()?;
+    let descriptors = generate_descriptors(&generated_functions, &impl_blocks, &cx, &mod_name)?;
+
+    // Generate the caller of another contract if requested
+    let caller = options
+        .gen_caller_for
+        .as_ref()
+        .map(|trait_name| generate_caller(trait_name, &input_mod))
+        .transpose()?;
 
     // Combine all pieces into the final output
+    let generated = quote! {
+        #init_function
+        #deploy_args
+        #(#generated_functions)*
+        #snapshot
+        #descriptors
+        #caller
+    };
+
+    // `parse_contract` ensures the module has a body
+    let (_, items) = input_mod.content.as_mut().unwrap();
+    items.push(Item::Verbatim(generated));
+
+    // Gate the module, and the state and wrappers emitted in it, behind the feature
+    if let Some(cfg) = options.cfg() {
+        input_mod.attrs.push(syn::parse_quote! { #cfg });
+    }
+
+    // Embed the expansion into the module for inspection if requested
+    if options.debug {
+        let debug = input_mod.to_token_stream().to_string();
+        if let Some((_, items)) = &mut input_mod.content {
+            items.push(syn::parse_quote! {
+                /// The code generated by the `#[contract]` macro, for debugging purposes.
+                pub const _DEBUG: &str = #debug;
+            });
+        }
+    }
+
+    Ok(input_mod.into_token_stream())
+}
+
+/// Expands an `impl` block of the contract's state, generating the wrappers of its
+/// methods.
+///
+/// In this mode, the user declares the state of the contract as a `static mut STATE` in
+/// the scope of the `impl` block, and the wrappers are emitted next to the block:
+///
+/// ```ignore
+/// static mut STATE: Counter = Counter { value: 0 };
+///
+/// #[contract]
+/// impl Counter {
+///     pub fn read_value(&self) -> i64 {
+///         self.value
+///     }
+/// }
+/// ```
+///
+/// # Errors
+/// - If an option that only applies to contract modules is passed.
+/// - If the `impl` block isn't written for a type named by an identifier.
+fn expand_impl(
+    options: ContractOptions,
+    mut input_impl: ItemImpl,
+) -> syn::Result<proc_macro2::TokenStream> {
+    options.validate_for_impl()?;
+    let (struct_name, impl_block) = parse_contract_impl(&mut input_impl, &options)?;
+
+    let cx = ExportContext {
+        struct_name: &struct_name,
+        state: quote! { STATE },
+        options: &options,
+        symbols: &[],
+    };
+
+    let impl_blocks = [impl_block];
+    let generated_functions = generate_public_functions(&impl_blocks, &cx)?;
+    let descriptors = generate_descriptors(&generated_functions, &impl_blocks, &cx, &struct_name)?;
+
     let expanded = quote! {
-        #input_mod
+        #input_impl
         #(#generated_functions)*
+        #descriptors
     };
 
-    expanded.into()
+    // Gate the block and each wrapper emitted next to it behind the feature
+    match options.cfg() {
+        Some(cfg) => {
+            let file: File = syn::parse2(expanded)?;
+            let items = file.items.iter();
+            Ok(quote! { #(#cfg #items)* })
+        }
+        None => Ok(expanded),
+    }
+}
+
+/// Generates the exports describing the contract, as requested by the options.
+///
+/// # Errors
+/// If the `require_methods` option is set and no method is exported. The error is
+/// spanned on the given tokens.
+fn generate_descriptors(
+    generated_functions: &[proc_macro2::TokenStream],
+    impl_blocks: &[ItemImpl],
+    cx: &ExportContext,
+    spanned: &Ident,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let options = cx.options;
+    if options.require_methods && generated_functions.is_empty() {
+        return Err(syn::Error::new_spanned(
+            spanned,
+            "The contract doesn't export any method. Declare at least one method of the contract's state as `pub` to generate an entry point for it.",
+        ));
+    }
+
+    // Generate the ABI descriptor if requested
+    let abi_function = options
+        .abi_export
+        .then(|| generate_abi_function(impl_blocks, cx.struct_name, options));
+    let abi_json = options
+        .abi_json
+        .then(|| generate_abi_json(impl_blocks, cx.struct_name, options));
+    let name_function = options
+        .embed_name
+        .then(|| generate_name_function(cx.struct_name));
+
+    Ok(quote! {
+        #abi_function
+        #abi_json
+        #name_function
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+    use proc_macro2::TokenStream;
+    use quote::{quote, ToTokens};
+    use syn::{Expr, ExprLit, File, ImplItem, ImplItemFn, Item, ItemImpl, ItemMod, Lit};
+
+    /// Returns a `counter` contract module, declaring the state struct `Counter` with the
+    /// given methods, followed by the given items.
+    fn counter(methods: TokenStream, items: TokenStream) -> TokenStream {
+        quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    #methods
+                }
+
+                #items
+            }
+        }
+    }
+
+    /// Returns the contract module emitted by an expansion.
+    fn contract_module(expanded: TokenStream) -> ItemMod {
+        let file: File = syn::parse2(expanded).unwrap();
+        file.items
+            .into_iter()
+            .find_map(|item| match item {
+                Item::Mod(module) => Some(module),
+                _ => None,
+            })
+            .expect("expected the contract module")
+    }
+
+    /// Returns the items of a module.
+    fn module_items(module: &ItemMod) -> &[Item] {
+        &module.content.as_ref().unwrap().1
+    }
+
+    /// Returns the `impl` block of a module declaring the item with the given name.
+    fn impl_declaring<'a>(items: &'a [Item], name: &str) -> &'a ItemImpl {
+        items
+            .iter()
+            .find_map(|item| match item {
+                Item::Impl(imp)
+                    if imp.items.iter().any(|item| match item {
+                        ImplItem::Fn(method) => method.sig.ident == name,
+                        ImplItem::Type(ty) => ty.ident == name,
+                        _ => false,
+                    }) =>
+                {
+                    Some(imp)
+                }
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("expected an `impl` block declaring `{name}`"))
+    }
+
+    /// Returns the names of the functions of a module, in order.
+    fn function_names(items: &[Item]) -> Vec<String> {
+        items
+            .iter()
+            .filter_map(|item| match item {
+                Item::Fn(function) => Some(function.sig.ident.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_require_methods_rejects_contract_without_methods() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let error = expand(quote!(require_methods), item.clone()).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("The contract doesn't export any method"));
+
+        assert!(expand(quote!(), item).is_ok());
+    }
+
+    #[test]
+    fn test_debug_embeds_expansion() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let module = contract_module(expand(quote!(debug), item.clone()).unwrap());
+        let debug = module_items(&module)
+            .iter()
+            .find_map(|item| match item {
+                Item::Const(item) if item.ident == "_DEBUG" => Some(item.expr.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let debug = match *debug {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => lit.value(),
+            _ => panic!("`_DEBUG` must be a string literal"),
+        };
+
+        let wrapper = quote! { pub unsafe fn read_value(arg_len: u32) -> u32 };
+        assert!(debug.contains(&wrapper.to_string()));
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+        assert!(!expanded.contains("_DEBUG"));
+    }
+
+    #[test]
+    fn test_runtime_init_generates_init_export() {
+        let item = counter(
+            quote! {
+                pub fn new(value: i64) -> Self {
+                    Self { value }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        assert!(expanded.contains(
+            &quote! { pub(crate) static mut STATE: Option<Counter> = None; }.to_string()
+        ));
+        assert!(expanded.contains(&quote! { STATE = Some(Counter::new(value)); }.to_string()));
+        assert!(expanded.contains(&quote! { pub unsafe fn init(arg_len: u32) -> u32 }.to_string()));
+        assert!(expanded.contains(&quote! { pub fn new(value: i64) -> Self }.to_string()));
+    }
+
+    #[test]
+    fn test_helper_attributes_are_stripped() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                #[view]
+                #[inline]
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn vote(&mut self, #[caller] caller: Option<ContractId>) {}
+            },
+            quote! {
+                #[export]
+                pub fn double(value: i64) -> i64 {
+                    value * 2
+                }
+            },
+        );
+
+        let expanded = expand(quote!(export_fns), item).unwrap().to_string();
+
+        assert!(!expanded.contains(&quote!(#[view]).to_string()));
+        assert!(!expanded.contains(&quote!(#[export]).to_string()));
+        assert!(!expanded.contains(&quote!(#[caller]).to_string()));
+        assert!(expanded.contains(&quote!(#[inline]).to_string()));
+        assert!(expanded
+            .contains(&quote! { pub unsafe fn read_value(arg_len: u32) -> u32 }.to_string()));
+    }
+
+    #[test]
+    fn test_embed_name_uses_struct_name() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(embed_name), item.clone())
+            .unwrap()
+            .to_string();
+
+        assert!(expanded
+            .contains(&quote! { pub unsafe fn __contract_name(arg_len: u32) -> u32 }.to_string()));
+        assert!(expanded.contains(&quote! { alloc::string::String::from("Counter") }.to_string()));
+        assert!(!expanded.contains(&quote! { alloc::string::String::from("counter") }.to_string()));
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+        assert!(!expanded.contains("__contract_name"));
+    }
+
+    #[test]
+    fn test_snapshot_returns_clone_of_state() {
+        let item = quote! {
+            pub mod counter {
+                #[derive(Clone, Debug)]
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(snapshot), item).unwrap().to_string();
+
+        assert!(
+            expanded.contains(&quote! { pub unsafe fn snapshot(arg_len: u32) -> u32 }.to_string())
+        );
+        assert!(expanded.contains(&quote! { |(): ()| STATE.clone() }.to_string()));
+    }
+
+    #[test]
+    fn test_snapshot_requires_clone() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote!(),
+        );
+
+        let error = expand(quote!(snapshot), item).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The `snapshot` option requires the contract state to implement `Clone`. Add `#[derive(Clone)]` to `Counter`."
+        );
+
+        // A manual implementation in the module is accepted
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                impl core::clone::Clone for Counter {
+                    fn clone(&self) -> Self {
+                        Self { value: self.value }
+                    }
+                }
+            },
+        );
+        assert!(expand(quote!(snapshot), item).is_ok());
+    }
+
+    #[test]
+    fn test_no_state_only_exports_static_methods() {
+        let item = quote! {
+            pub mod hasher {
+                pub struct Hasher;
+
+                impl Hasher {
+                    pub fn new(seed: u64) -> u64 {
+                        seed
+                    }
+
+                    pub fn digest(data: Vec<u8>) -> u64 {
+                        Self::mix(&data)
+                    }
+
+                    fn mix(data: &[u8]) -> u64 {
+                        data.iter().map(|byte| *byte as u64).sum()
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(no_state), item).unwrap();
+
+        let module = contract_module(expanded);
+        assert!(!module_items(&module)
+            .iter()
+            .any(|item| matches!(item, Item::Static(item) if item.ident == "STATE")));
+        let expanded = module.to_token_stream().to_string();
+        assert!(
+            expanded.contains(&quote! { pub unsafe fn digest(arg_len: u32) -> u32 }.to_string())
+        );
+        // `new` isn't a constructor without a state
+        assert!(expanded.contains(&quote! { pub unsafe fn new(arg_len: u32) -> u32 }.to_string()));
+        assert!(!expanded.contains(&quote! { pub unsafe fn init }.to_string()));
+    }
+
+    #[test]
+    fn test_no_state_accepts_module_without_struct() {
+        let item = quote! {
+            pub mod hasher {
+                pub fn digest(data: Vec<u8>) -> u64 {
+                    data.iter().map(|byte| *byte as u64).sum()
+                }
+            }
+        };
+
+        let expanded = expand(quote!(no_state, export_fns), item)
+            .unwrap()
+            .to_string();
+
+        assert!(expanded.contains(&quote! { #[export_name = "digest"] }.to_string()));
+        assert!(!expanded.contains("STATE"));
+    }
+
+    #[test]
+    fn test_no_state_rejects_methods_accessing_state() {
+        let item = quote! {
+            pub mod hasher {
+                pub struct Hasher;
+
+                impl Hasher {
+                    pub fn digest(&self, data: Vec<u8>) -> u64 {
+                        data.len() as u64
+                    }
+                }
+            }
+        };
+
+        let error = expand(quote!(no_state), item).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "The method `digest` accesses the contract state, which the `no_state` option doesn't declare. Only associated functions without `self` are exported by stateless contracts."
+        );
+    }
+
+    #[test]
+    fn test_const_new_delegating_to_helper_rewrites_self() {
+        let item = quote! {
+            pub mod counter {
+                pub struct Counter {
+                    supply: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self::with_supply(1000)
+                    }
+
+                    const fn with_supply(supply: u64) -> Self {
+                        Self { supply }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        assert!(expanded.contains(
+            &quote! { pub(crate) static mut STATE: Counter = Counter::with_supply(1000); }
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn test_const_struct_update_is_inlined_intact() {
+        let item = quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                    supply: u64,
+                }
+
+                impl Counter {
+                    const EMPTY: Self = Self { value: 0, supply: 0 };
+
+                    pub const fn new() -> Self {
+                        let supply = 1000;
+                        Self { supply, ..Self::EMPTY }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        let expected = quote! {
+            pub(crate) static mut STATE: Counter = {
+                let supply = 1000;
+                Counter { supply, ..Counter::EMPTY }
+            };
+        };
+        assert!(expanded.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_const_builder_new_rewrites_self_in_chain() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self::builder().value(5).build()
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        let expected = quote! {
+            pub(crate) static mut STATE: Counter = Counter::builder().value(5).build();
+        };
+        assert!(expanded.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_boxed_state_is_initialized_lazily() {
+        let item = quote! {
+            pub mod counter {
+                pub struct Counter {
+                    values: [u64; 4096],
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { values: [0; 4096] }
+                    }
+
+                    pub fn read_value(&self, index: usize) -> u64 {
+                        self.values[index]
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(boxed_state), item).unwrap().to_string();
+
+        assert!(expanded.contains(
+            &quote! { pub(crate) static mut STATE: Option<alloc::boxed::Box<Counter> > = None; }
+                .to_string()
+        ));
+        assert!(expanded.contains(
+            &quote! {
+                (**STATE
+                    .get_or_insert_with(|| alloc::boxed::Box::new(Counter::new())))
+                .read_value(index)
+            }
+            .to_string()
+        ));
+        // The constructor is kept to be called at runtime
+        assert!(expanded.contains(&quote! { pub const fn new() -> Self }.to_string()));
+    }
+
+    #[test]
+    fn test_init_name_renames_constructor_export() {
+        let item = counter(
+            quote! {
+                pub fn new(value: i64) -> Self {
+                    Self { value }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(init_name = "deploy"), item)
+            .unwrap()
+            .to_string();
+
+        assert!(
+            expanded.contains(&quote! { pub unsafe fn deploy(arg_len: u32) -> u32 }.to_string())
+        );
+        assert!(expanded.contains(&quote! { STATE = Some(Counter::new(value)); }.to_string()));
+        assert!(expanded.contains("the contract state must be initialized by calling `deploy`"));
+        assert!(!expanded.contains("fn init"));
+    }
+
+    #[test]
+    fn test_client_generates_deploy_args() {
+        let item = quote! {
+            pub mod token {
+                pub struct Token {
+                    supply: u64,
+                    owner: ContractId,
+                    name: String,
+                }
+
+                impl Token {
+                    pub fn new(supply: u64, owner: ContractId, name: String) -> Self {
+                        Self { supply, owner, name }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(client), item.clone()).unwrap().to_string();
+        let deploy_args = quote! {
+            pub struct DeployArgs {
+                pub supply: u64,
+                pub owner: ContractId,
+                pub name: String,
+            }
+
+            impl DeployArgs {
+                #[doc = " Returns the arguments as decoded by the `init` export."]
+                pub fn into_init_arg(self) -> (u64, ContractId, String) {
+                    (self.supply, self.owner, self.name)
+                }
+            }
+        };
+        assert!(expanded.contains(&deploy_args.to_string()));
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+        assert!(!expanded.contains("DeployArgs"));
+    }
+
+    #[test]
+    fn test_init_guard_prevents_double_initialization() {
+        let item = counter(
+            quote! {
+                pub fn new(value: i64) -> Self {
+                    Self { value }
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(init_guard), item.clone())
+            .unwrap()
+            .to_string();
+        let guard = quote! {
+            static mut INITIALIZED: bool = false;
+        };
+        assert!(expanded.contains(&guard.to_string()));
+        let init = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(value): (i64)| {
+                if INITIALIZED {
+                    panic!("the contract state is already initialized by `init`");
+                }
+                STATE = Some(Counter::new(value));
+                INITIALIZED = true;
+            })
+        };
+        assert!(expanded.contains(&init.to_string()));
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+        assert!(!expanded.contains("INITIALIZED"));
+    }
+
+    #[test]
+    fn test_init_attribute_constructor_is_called_by_init() {
+        let item = counter(
+            quote! {
+                pub fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                #[init]
+                pub fn with_value(value: i64) -> Self {
+                    Self { value }
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        assert!(
+            expanded.contains(&quote! { STATE = Some(Counter::with_value(value)); }.to_string())
+        );
+        assert!(expanded.contains(&quote! { Counter::new() }.to_string()));
+        assert!(expanded.contains(&quote! { pub unsafe fn new(arg_len: u32) -> u32 }.to_string()));
+        assert!(!expanded.contains("fn with_value (arg_len"));
+        assert!(!expanded.contains("# [init]"));
+    }
+
+    #[test]
+    fn test_const_generic_state_uses_defaults() {
+        let item = quote! {
+            pub mod buffer {
+                pub struct Buffer<const N: usize = 32> {
+                    data: [u8; N],
+                }
+
+                impl Buffer {
+                    pub const fn new() -> Self {
+                        Self { data: [0; 32] }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        let expected = quote! {
+            pub(crate) static mut STATE: Buffer<{ 32 }> = Buffer { data: [0; 32] };
+        };
+        assert!(expanded.contains(&expected.to_string()));
+    }
+
+    #[test]
+    fn test_generic_state_without_default_is_rejected() {
+        let item = quote! {
+            pub mod buffer {
+                pub struct Buffer<const N: usize> {
+                    data: [u8; N],
+                }
+
+                impl Buffer<32> {
+                    pub const fn new() -> Self {
+                        Self { data: [0; 32] }
+                    }
+                }
+            }
+        };
+
+        let error = expand(quote!(), item).unwrap_err();
+
+        assert!(error.to_string().contains(
+            "The generic parameter `N` of the contract's state `Buffer` must have a default"
+        ));
+    }
+
+    #[test]
+    fn test_default_expansion_does_not_require_alloc() {
+        let item = quote! {
+            pub mod counter {
+                pub struct Counter {
+                    owner: ContractId,
+                    values: [u64; 4],
+                }
+
+                impl Counter {
+                    pub fn new(owner: ContractId) -> Self {
+                        Self {
+                            owner,
+                            values: [0; 4],
+                        }
+                    }
+
+                    pub fn read_value(&self, index: usize) -> u64 {
+                        self.values[index]
+                    }
+
+                    #[only_owner]
+                    pub fn set_value(&mut self, index: usize, value: u64) {
+                        self.values[index] = value;
+                    }
+                }
+
+                #[export]
+                impl Reset for Counter {
+                    fn reset(&mut self) {
+                        self.values = [0; 4];
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(owner_field = "owner"), item.clone())
+            .unwrap()
+            .to_string();
+        assert!(!expanded.contains("alloc"));
+
+        // Only the options returning heap-allocated values depend on `alloc`
+        for option in [quote!(abi_export), quote!(boxed_state), quote!(embed_name)] {
+            let options = quote!(owner_field = "owner", #option);
+            let expanded = expand(options, item.clone()).unwrap().to_string();
+            assert!(expanded.contains("alloc ::"));
+        }
+    }
+
+    #[test]
+    fn test_interleaved_private_methods_are_kept_but_not_exported() {
+        let item = counter(
+            quote! {
+                fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn increment(&mut self) {
+                    self.bump(1);
+                }
+
+                fn bump(&mut self, by: i64) {
+                    self.value += by;
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.checked()
+                }
+
+                fn checked(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+
+        // The private helpers are emitted as declared
+        let imp = impl_declaring(items, "bump");
+        let bump = quote! {
+            fn bump(&mut self, by: i64) {
+                self.value += by;
+            }
+        };
+        let checked = quote! {
+            fn checked(&self) -> i64 {
+                self.value
+            }
+        };
+        let emitted = quote!(#imp).to_string();
+        assert!(emitted.contains(&bump.to_string()));
+        assert!(emitted.contains(&checked.to_string()));
+
+        // Only the public methods are exported, and the private `new` is the constructor
+        assert_eq!(function_names(items), ["increment", "read_value"]);
+    }
+
+    #[test]
+    fn test_drop_impl_is_not_exported() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote! {
+                impl Drop for Counter {
+                    fn drop(&mut self) {
+                        self.value = 0;
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        assert!(items
+            .iter()
+            .any(|item| matches!(item, Item::Impl(imp) if imp.trait_.is_some())));
+
+        assert_eq!(function_names(items), ["read_value"]);
+    }
+
+    #[test]
+    fn test_doc_hidden_impl_block_is_exported() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                #[doc(hidden)]
+                impl Counter {
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        let imp = impl_declaring(items, "read_value");
+        assert!(imp.attrs[0].path().is_ident("doc"));
+
+        assert_eq!(function_names(items), ["read_value"]);
+    }
+
+    #[test]
+    fn test_generic_helper_methods_are_preserved() {
+        let helper: ImplItemFn = syn::parse_quote! {
+            fn scaled<T: Into<i64>>(&self, factor: T) -> i64 {
+                self.value * factor.into()
+            }
+        };
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn add(&mut self, value: i64) {
+                    self.value += value;
+                }
+
+                #helper
+
+                pub fn doubled(&self) -> i64 {
+                    self.scaled(2u8)
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        let imp = impl_declaring(items, "scaled");
+        let helpers: Vec<_> = imp
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                ImplItem::Fn(method) if method.sig.ident == "scaled" => Some(method),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(helpers, [&helper]);
+
+        // Only the concrete public methods get a wrapper
+        assert_eq!(function_names(items), ["add", "doubled"]);
+    }
+
+    #[test]
+    fn test_associated_types_of_trait_impls_are_preserved() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                #[export]
+                impl Iterator for Counter {
+                    type Item = i64;
+
+                    fn next(&mut self) -> Option<Self::Item> {
+                        self.value += 1;
+                        Some(self.value)
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        let imp = impl_declaring(items, "Item");
+        let item_type = imp
+            .items
+            .iter()
+            .find(|item| matches!(item, ImplItem::Type(_)))
+            .unwrap();
+        assert_eq!(
+            quote!(#item_type).to_string(),
+            quote! { type Item = i64; }.to_string()
+        );
+
+        // Only the method of the implementation gets a wrapper
+        assert_eq!(function_names(items), ["next"]);
+    }
+
+    #[test]
+    fn test_trait_impls_are_not_exported_unless_opted_in() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote! {
+                impl core::fmt::Debug for Counter {
+                    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                        write!(f, "Counter({})", self.value)
+                    }
+                }
+
+                impl PartialEq for Counter {
+                    fn eq(&self, other: &Self) -> bool {
+                        self.value == other.value
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        assert_eq!(function_names(items), ["read_value"]);
+    }
+
+    #[test]
+    fn test_local_trait_impls_are_not_exported_unless_opted_in() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                pub trait Describe {
+                    fn describe(&self) -> i64;
+                }
+
+                impl Describe for Counter {
+                    fn describe(&self) -> i64 {
+                        self.value
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let items = module_items(&module);
+        assert!(function_names(items).is_empty());
+        assert!(!module.to_token_stream().to_string().contains("wrap_call"));
+    }
+
+    #[test]
+    fn test_export_attribute_of_trait_impl_is_stripped() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                #[export]
+                impl Default for Counter {
+                    fn default() -> Self {
+                        Self::new()
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        assert!(!expanded.contains("# [export]"));
+        let wrapper = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| <Counter as Default>::default())
+        };
+        assert!(expanded.contains(&wrapper.to_string()));
+    }
+
+    #[test]
+    fn test_trait_definitions_are_preserved() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote! {
+                pub trait Describe {
+                    fn describe(&self) -> i64;
+                }
+
+                #[export]
+                impl Describe for Counter {
+                    fn describe(&self) -> i64 {
+                        self.value
+                    }
+                }
+            },
+        );
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        let definition = quote! {
+            pub trait Describe {
+                fn describe(&self) -> i64;
+            }
+        };
+        assert!(expanded.contains(&definition.to_string()));
+        assert_eq!(expanded.matches("trait Describe").count(), 1);
+
+        // The methods implementing the trait are exported, as the block is marked `#[export]`
+        let wrapper = quote! {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| <Counter as Describe>::describe(&STATE))
+        };
+        assert!(expanded.contains(&wrapper.to_string()));
+    }
+
+    #[test]
+    fn test_restricted_state_keeps_its_visibility() {
+        let item = quote! {
+            pub mod counter {
+                pub(super) struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(allow_restricted_state), item)
+            .unwrap()
+            .to_string();
+
+        let state = quote! {
+            pub(super) static mut STATE: Counter = Counter { value: 0 };
+        };
+        assert!(expanded.contains(&state.to_string()));
+    }
+
+    #[test]
+    fn test_user_declared_state_is_not_duplicated() {
+        let item = |state: TokenStream| {
+            counter(
+                quote! {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                },
+                quote! {
+                    #[link_section = ".state"]
+                    #state
+                },
+            )
+        };
+
+        let expanded = expand(
+            quote!(),
+            item(quote! { static mut STATE: Counter = Counter { value: 1 }; }),
+        )
+        .unwrap()
+        .to_string();
+        assert_eq!(expanded.matches("static mut STATE").count(), 1);
+        assert!(expanded.contains(
+            &quote! {
+                #[link_section = ".state"]
+                static mut STATE: Counter = Counter { value: 1 };
+            }
+            .to_string()
+        ));
+        assert!(expanded.contains(
+            &quote! { dusk_core::abi::wrap_call(arg_len, |(): ()| STATE.read_value()) }.to_string()
+        ));
+
+        let error = expand(
+            quote!(),
+            item(quote! { static mut STATE: Option<Counter> = None; }),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The `STATE` declared by the module must have the type `Counter`, through which the generated wrappers access the contract state."
+        );
+    }
+
+    #[test]
+    fn test_max_state_size_asserts_state_size() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(max_state_size = 4096), item.clone())
+            .unwrap()
+            .to_string();
+        let assertion = quote! {
+            const _: () = assert!(
+                core::mem::size_of::<Counter>() <= 4096usize,
+                "the contract state exceeds the size set by `max_state_size`"
+            );
+        };
+        assert!(expanded.contains(&assertion.to_string()));
+
+        let error = expand(quote!(max_state_size = "4096"), item.clone())
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "expected integer literal");
+
+        let error = expand(quote!(max_state_size = -1), item).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`max_state_size` must be a size in bytes, e.g. `max_state_size = 4096`"
+        );
+    }
+
+    #[test]
+    fn test_state_section_places_state_in_link_section() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(state_section = ".contract_state"), item.clone())
+            .unwrap()
+            .to_string();
+        let state = quote! {
+            #[link_section = ".contract_state"]
+            pub(crate) static mut STATE: Counter = Counter { value: 0 };
+        };
+        assert!(expanded.contains(&state.to_string()));
+
+        let expanded = expand(quote!(), item.clone()).unwrap().to_string();
+        assert!(!expanded.contains("link_section"));
+
+        let error = expand(quote!(state_section = contract_state), item)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "expected string literal");
+    }
+
+    #[test]
+    fn test_inner_attributes_are_preserved() {
+        let item = quote! {
+            pub mod counter {
+                #![allow(dead_code)]
+
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+            }
+        };
+
+        let expanded = expand(quote!(), item).unwrap();
+
+        let module = contract_module(expanded);
+        let inner: syn::Attribute = syn::parse_quote! { #![allow(dead_code)] };
+        assert_eq!(module.attrs, [inner]);
+        // The generated items are appended after the items of the module
+        let items = module_items(&module);
+        let position = |matches: fn(&Item) -> bool| items.iter().position(matches).unwrap();
+        assert!(
+            position(|item| matches!(item, Item::Struct(item) if item.ident == "Counter"))
+                < position(|item| matches!(item, Item::Static(item) if item.ident == "STATE"))
+        );
+    }
+
+    #[test]
+    fn test_contract_on_impl_block_uses_user_state() {
+        let item = quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn add(&mut self, amount: i64) {
+                    self.value += amount;
+                }
+
+                pub fn zero() -> i64 {
+                    0
+                }
+            }
+        };
+
+        let expanded = expand(quote!(), item).unwrap().to_string();
+
+        assert!(expanded.contains(&quote! { impl Counter }.to_string()));
+        assert!(expanded.contains(&quote! { |(amount): (i64)| STATE.add(amount) }.to_string()));
+        assert!(expanded.contains(&quote! { STATE.read_value() }.to_string()));
+        assert!(expanded.contains(&quote! { Counter::zero() }.to_string()));
+        assert!(!expanded.contains("static mut STATE"));
+    }
+
+    #[test]
+    fn test_contract_on_impl_block_rejects_module_options() {
+        let item = quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            }
+        };
+
+        let error = expand(quote!(export_fns), item).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "the `export_fns` option is only supported on contract modules"
+        );
+    }
+
+    #[test]
+    fn test_feature_gates_module_and_generated_items() {
+        let item = counter(
+            quote! {
+                pub fn new(value: i64) -> Self {
+                    Self { value }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote!(),
+        );
+
+        let expanded = expand(quote!(feature = "tokencontract", abi_export), item).unwrap();
+
+        let file: File = syn::parse2(expanded.clone()).unwrap();
+        let module = contract_module(expanded);
+        let cfg: syn::Attribute = syn::parse_quote! { #[cfg(feature = "tokencontract")] };
+        assert!(module.attrs.contains(&cfg));
+        // The state and all wrappers are emitted in the gated module
+        assert_eq!(file.items.len(), 1);
+        let module = quote!(#module).to_string();
+        assert!(module.contains("static mut STATE"));
+        assert!(module.contains("fn init"));
+        assert!(module.contains("fn read_value (arg_len"));
+        assert!(module.contains("fn __abi"));
+    }
+
+    #[test]
+    fn test_feature_gates_impl_block_and_wrappers() {
+        let item = quote! {
+            impl Counter {
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+
+                pub fn zero() -> i64 {
+                    0
+                }
+            }
+        };
+
+        let expanded = expand(quote!(feature = "tokencontract", embed_name), item).unwrap();
+
+        let file: File = syn::parse2(expanded).unwrap();
+        let cfg: syn::Attribute = syn::parse_quote! { #[cfg(feature = "tokencontract")] };
+        assert_eq!(file.items.len(), 4);
+        for item in &file.items {
+            let attrs = match item {
+                Item::Impl(imp) => &imp.attrs,
+                Item::Fn(func) => &func.attrs,
+                _ => panic!("unexpected generated item"),
+            };
+            assert!(attrs.contains(&cfg));
+        }
+    }
+
+    #[test]
+    fn test_user_no_mangle_function_colliding_with_export_is_rejected() {
+        let item = counter(
+            quote! {
+                pub const fn new() -> Self {
+                    Self { value: 0 }
+                }
+
+                pub fn read_value(&self) -> i64 {
+                    self.value
+                }
+            },
+            quote! {
+                #[export_name = "read_value"]
+                unsafe fn read(arg_len: u32) -> u32 {
+                    arg_len
+                }
+            },
+        );
+
+        let error = expand(quote!(), item).unwrap_err();
+
+        let messages: Vec<_> = error.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains(
+            "The method `read_value` is exported under the same symbol as a `no_mangle` function"
+        ));
+    }
+
+    #[test]
+    fn test_contract_on_other_items_is_rejected() {
+        let error = expand(quote!(), quote! { pub fn read_value() -> i64 { 0 } }).unwrap_err();
+
+        assert!(error
+            .to_string()
+            .contains("`#[contract]` must be applied to a module, or to an `impl` block"));
+    }
 }
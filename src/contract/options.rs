@@ -0,0 +1,419 @@
+use crate::contract::selector::SelectorScheme;
+use alloc::format;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::parse::Parser;
+use syn::{Ident, LitInt, LitStr};
+
+/// The options supported by the `#[contract(...)]` attribute.
+const SUPPORTED_OPTIONS: &[&str] = &[
+    "abi_export",
+    "abi_json",
+    "allow_restricted_state",
+    "auto_clone_returns",
+    "borrow_args",
+    "boxed_state",
+    "caller_arg",
+    "client",
+    "debug",
+    "deny_panics",
+    "deposit_query",
+    "embed_name",
+    "entry",
+    "export_fns",
+    "feature",
+    "gen_caller_for",
+    "init_guard",
+    "init_name",
+    "keep_exports",
+    "log_calls",
+    "max_state_size",
+    "namespace_traits",
+    "no_state",
+    "owner_field",
+    "pre",
+    "require_methods",
+    "selector",
+    "snapshot",
+    "state_section",
+    "strict",
+    "test_exports",
+];
+
+/// Options passed to the `#[contract(...)]` attribute.
+///
+/// # Example
+/// ```ignore
+/// #[contract(abi_export)]
+/// pub mod counter { /* ... */ }
+/// ```
+#[derive(Default)]
+pub struct ContractOptions {
+    /// Generates an `__abi` export describing the exported methods.
+    pub abi_export: bool,
+    /// Generates a `CONTRACT_ABI_JSON` constant describing the exported methods as JSON.
+    pub abi_json: bool,
+    /// Accepts a struct with a restricted visibility, e.g. `pub(crate)`, as the state.
+    pub allow_restricted_state: bool,
+    /// Returns an owned copy of the `&str` and `&[T]` references returned by methods.
+    pub auto_clone_returns: bool,
+    /// Decodes the arguments taken by shared reference as owned values, passing a reference
+    /// to them to the method.
+    pub borrow_args: bool,
+    /// Allocates the state on the heap, as `static mut STATE: Option<Box<MyStruct>>`.
+    pub boxed_state: bool,
+    /// Passes the caller of the contract to a first method argument named `caller`, as if
+    /// it were marked `#[caller]`.
+    pub caller_arg: bool,
+    /// Generates a `DeployArgs` struct holding the arguments of the constructor, for the
+    /// code deploying the contract.
+    pub client: bool,
+    /// The associated function of the state called by every wrapper before the method,
+    /// which aborts the call by returning an error.
+    pub pre: Option<Ident>,
+    /// Reports an error if the contract doesn't export any method.
+    pub require_methods: bool,
+    /// The scheme deriving the selectors of the `entry` dispatch, the index of the method
+    /// by default.
+    pub selector: Option<SelectorScheme>,
+    /// Generates a `snapshot` export returning a clone of the contract state.
+    pub snapshot: bool,
+    /// The link section in which the generated `STATE` is placed.
+    pub state_section: Option<LitStr>,
+    /// Reports an error for code that is otherwise silently not exported or not seen by
+    /// the macro, instead of ignoring it.
+    pub strict: bool,
+    /// Generates a plain `__call_` function for each exported method, calling it natively
+    /// for host-side tests.
+    pub test_exports: bool,
+    /// Generates `no_mangle` wrappers for the public free functions of the module.
+    pub export_fns: bool,
+    /// The feature gating the contract and all code generated for it.
+    pub feature: Option<LitStr>,
+    /// Embeds the generated code as a `_DEBUG` string constant in the module.
+    pub debug: bool,
+    /// Requires the exported methods to return a `Result`, and returns the errors of their
+    /// validators instead of aborting the call.
+    pub deny_panics: bool,
+    /// The query of the transfer contract returning the deposit of the transaction, called
+    /// by the methods that aren't `#[payable]` to abort calls depositing a value.
+    pub deposit_query: Option<LitStr>,
+    /// Generates a `__contract_name` export returning the name of the state struct.
+    pub embed_name: bool,
+    /// The name of a single export dispatching to the methods by selector, instead of
+    /// exporting each method.
+    pub entry: Option<Ident>,
+    /// The trait of the module for which a caller struct, calling its methods on another
+    /// contract, is generated.
+    pub gen_caller_for: Option<Ident>,
+    /// Aborts the `init` export if the state was already initialized by a previous call.
+    pub init_guard: bool,
+    /// The name of the export initializing the state at runtime, `init` by default.
+    pub init_name: Option<Ident>,
+    /// References each export from a `#[used]` static, so that the linker doesn't strip it.
+    pub keep_exports: bool,
+    /// Logs the name and the argument length of each call to the host, in debug builds.
+    /// Requires the `abi-debug` feature of `dusk-core`, which provides `piecrust_debug!`.
+    pub log_calls: bool,
+    /// The maximum size of the state in bytes, asserted at compile time.
+    pub max_state_size: Option<usize>,
+    /// Prefixes the exports of trait methods with the name of the trait, e.g. `TraitA_value`.
+    pub namespace_traits: bool,
+    /// Declares no contract state, only exporting the associated functions of the struct,
+    /// which is then optional.
+    pub no_state: bool,
+    /// The state field holding the `ContractId` of the owner, checked by methods marked
+    /// `#[only_owner]`.
+    pub owner_field: Option<Ident>,
+}
+
+impl ContractOptions {
+    /// Parses the options from the tokens of the `#[contract(...)]` attribute.
+    ///
+    /// # Errors
+    /// If an option is not supported or is malformed. The error for an unsupported option
+    /// lists the supported ones.
+    pub fn parse(attr: TokenStream) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        let parser = syn::meta::parser(|meta| {
+            if meta.path.is_ident("abi_export") {
+                options.abi_export = true;
+                Ok(())
+            } else if meta.path.is_ident("abi_json") {
+                options.abi_json = true;
+                Ok(())
+            } else if meta.path.is_ident("allow_restricted_state") {
+                options.allow_restricted_state = true;
+                Ok(())
+            } else if meta.path.is_ident("auto_clone_returns") {
+                options.auto_clone_returns = true;
+                Ok(())
+            } else if meta.path.is_ident("borrow_args") {
+                options.borrow_args = true;
+                Ok(())
+            } else if meta.path.is_ident("boxed_state") {
+                options.boxed_state = true;
+                Ok(())
+            } else if meta.path.is_ident("caller_arg") {
+                options.caller_arg = true;
+                Ok(())
+            } else if meta.path.is_ident("client") {
+                options.client = true;
+                Ok(())
+            } else if meta.path.is_ident("require_methods") {
+                options.require_methods = true;
+                Ok(())
+            } else if meta.path.is_ident("pre") {
+                let name: LitStr = meta.value()?.parse()?;
+                options.pre = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("selector") {
+                let scheme: LitStr = meta.value()?.parse()?;
+                options.selector = Some(SelectorScheme::parse(&scheme)?);
+                Ok(())
+            } else if meta.path.is_ident("snapshot") {
+                options.snapshot = true;
+                Ok(())
+            } else if meta.path.is_ident("state_section") {
+                options.state_section = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("strict") {
+                options.strict = true;
+                Ok(())
+            } else if meta.path.is_ident("test_exports") {
+                options.test_exports = true;
+                Ok(())
+            } else if meta.path.is_ident("export_fns") {
+                options.export_fns = true;
+                Ok(())
+            } else if meta.path.is_ident("debug") {
+                options.debug = true;
+                Ok(())
+            } else if meta.path.is_ident("deny_panics") {
+                options.deny_panics = true;
+                Ok(())
+            } else if meta.path.is_ident("deposit_query") {
+                options.deposit_query = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("embed_name") {
+                options.embed_name = true;
+                Ok(())
+            } else if meta.path.is_ident("entry") {
+                let name: LitStr = meta.value()?.parse()?;
+                options.entry = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("feature") {
+                options.feature = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("gen_caller_for") {
+                let name: LitStr = meta.value()?.parse()?;
+                options.gen_caller_for = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("init_guard") {
+                options.init_guard = true;
+                Ok(())
+            } else if meta.path.is_ident("init_name") {
+                let name: LitStr = meta.value()?.parse()?;
+                options.init_name = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("keep_exports") {
+                options.keep_exports = true;
+                Ok(())
+            } else if meta.path.is_ident("log_calls") {
+                options.log_calls = true;
+                Ok(())
+            } else if meta.path.is_ident("max_state_size") {
+                let size: LitInt = meta.value()?.parse()?;
+                options.max_state_size = Some(size.base10_parse().map_err(|_| {
+                    syn::Error::new_spanned(
+                        &size,
+                        "`max_state_size` must be a size in bytes, e.g. `max_state_size = 4096`",
+                    )
+                })?);
+                Ok(())
+            } else if meta.path.is_ident("namespace_traits") {
+                options.namespace_traits = true;
+                Ok(())
+            } else if meta.path.is_ident("no_state") {
+                options.no_state = true;
+                Ok(())
+            } else if meta.path.is_ident("owner_field") {
+                let name: LitStr = meta.value()?.parse()?;
+                options.owner_field = Some(name.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("storage") {
+                Err(meta.error(
+                    "the `storage` option isn't supported, as the Dusk VM has no keyed storage: `piecrust-uplink` 0.17 persists the whole memory of a contract between calls. Hold the key-value state in a collection of the state struct instead, e.g. a `BTreeMap` field.",
+                ))
+            } else {
+                Err(meta.error(format!(
+                    "unsupported contract option `{}`, expected one of: {}",
+                    meta.path.to_token_stream(),
+                    SUPPORTED_OPTIONS.join(", ")
+                )))
+            }
+        });
+        parser.parse2(attr)?;
+
+        if options.selector.is_some() && options.entry.is_none() {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "the `selector` option requires the `entry` option, as methods are otherwise exported by name",
+            ));
+        }
+
+        if options.no_state {
+            options.reject_state_options()?;
+        }
+
+        Ok(options)
+    }
+
+    /// Rejects the options configuring the contract state with the `no_state` option.
+    ///
+    /// # Errors
+    /// If an option configuring the state, such as `boxed_state`, is set.
+    fn reject_state_options(&self) -> syn::Result<()> {
+        let state_options = [
+            ("boxed_state", self.boxed_state),
+            ("client", self.client),
+            ("init_guard", self.init_guard),
+            ("init_name", self.init_name.is_some()),
+            ("max_state_size", self.max_state_size.is_some()),
+            ("owner_field", self.owner_field.is_some()),
+            ("pre", self.pre.is_some()),
+            ("snapshot", self.snapshot),
+            ("state_section", self.state_section.is_some()),
+        ];
+        match state_options.iter().find(|(_, is_set)| *is_set) {
+            Some((name, _)) => Err(syn::Error::new(
+                Span::call_site(),
+                format!("the `{name}` option requires a contract state, which `no_state` disables"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the options for `#[contract]` applied to an `impl` block.
+    ///
+    /// # Errors
+    /// If an option that only applies to contract modules is set, as the state and the
+    /// free functions are then declared by the user.
+    pub fn validate_for_impl(&self) -> syn::Result<()> {
+        let module_options = [
+            ("allow_restricted_state", self.allow_restricted_state),
+            ("boxed_state", self.boxed_state),
+            ("client", self.client),
+            ("debug", self.debug),
+            ("export_fns", self.export_fns),
+            ("gen_caller_for", self.gen_caller_for.is_some()),
+            ("init_guard", self.init_guard),
+            ("init_name", self.init_name.is_some()),
+            ("max_state_size", self.max_state_size.is_some()),
+            ("no_state", self.no_state),
+            ("snapshot", self.snapshot),
+            ("state_section", self.state_section.is_some()),
+        ];
+        match module_options.iter().find(|(_, is_set)| *is_set) {
+            Some((name, _)) => Err(syn::Error::new(
+                Span::call_site(),
+                format!("the `{name}` option is only supported on contract modules"),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the `#[cfg(feature = "...")]` attribute gating the generated code, if the
+    /// `feature` option is set.
+    pub fn cfg(&self) -> Option<TokenStream> {
+        self.feature
+            .as_ref()
+            .map(|feature| quote! { #[cfg(feature = #feature)] })
+    }
+
+    /// Returns the name of the export initializing the state at runtime.
+    pub fn init_name(&self) -> Ident {
+        self.init_name
+            .clone()
+            .unwrap_or_else(|| Ident::new("init", Span::call_site()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ContractOptions;
+    use crate::contract::selector::SelectorScheme;
+    use alloc::string::ToString;
+    use quote::quote;
+
+    #[test]
+    fn test_unknown_option_lists_supported_options() {
+        let error = ContractOptions::parse(quote!(abi_export, stat = "Counter"))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "unsupported contract option `stat`, expected one of: abi_export, abi_json, allow_restricted_state, auto_clone_returns, borrow_args, boxed_state, caller_arg, client, debug, deny_panics, deposit_query, embed_name, entry, export_fns, feature, gen_caller_for, init_guard, init_name, keep_exports, log_calls, max_state_size, namespace_traits, no_state, owner_field, pre, require_methods, selector, snapshot, state_section, strict, test_exports"
+        );
+    }
+
+    #[test]
+    fn test_keyed_storage_is_rejected() {
+        let error = ContractOptions::parse(quote!(storage = "map"))
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().starts_with(
+            "the `storage` option isn't supported, as the Dusk VM has no keyed storage"
+        ));
+    }
+
+    #[test]
+    fn test_no_state_rejects_state_options() {
+        let error = ContractOptions::parse(quote!(no_state, snapshot))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "the `snapshot` option requires a contract state, which `no_state` disables"
+        );
+    }
+
+    #[test]
+    fn test_selector_requires_entry() {
+        let error = ContractOptions::parse(quote!(selector = "keccak"))
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "the `selector` option requires the `entry` option, as methods are otherwise exported by name"
+        );
+
+        let options = ContractOptions::parse(quote!(entry = "call", selector = "keccak")).unwrap();
+        assert_eq!(options.selector, Some(SelectorScheme::Keccak));
+    }
+
+    #[test]
+    fn test_init_name_must_be_an_identifier() {
+        let error = ContractOptions::parse(quote!(init_name = "deploy contract"))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("unexpected token"));
+
+        let options = ContractOptions::parse(quote!(init_name = "deploy")).unwrap();
+        assert_eq!(options.init_name(), "deploy");
+        assert_eq!(ContractOptions::default().init_name(), "init");
+    }
+
+    #[test]
+    fn test_options_are_parsed() {
+        let options = ContractOptions::parse(quote!(debug, owner_field = "owner")).unwrap();
+
+        assert!(options.debug);
+        assert!(!options.abi_export);
+        assert_eq!(options.owner_field.unwrap(), "owner");
+    }
+}
@@ -1,114 +1,2216 @@
+use crate::contract::functions::exported_methods;
+use crate::contract::options::ContractOptions;
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::vec::Vec;
-use proc_macro::TokenStream;
-use syn::{Expr, Ident, ImplItem, Item, ItemImpl, ItemMod, Visibility};
+use quote::ToTokens;
+use syn::{
+    visit_mut::VisitMut, Attribute, Block, Expr, ExprLit, FnArg, Generics, Ident, ImplItem,
+    ImplItemFn, Item, ItemFn, ItemImpl, ItemMod, ItemStruct, ItemUse, Lifetime, Lit, Meta,
+    StaticMutability, Stmt, Type, UseTree, Visibility,
+};
+
+/// The attributes interpreted by the `#[contract]` macro on the items of the module.
+///
+/// They are only meaningful to the macro, and are removed from the emitted module since
+/// the compiler would otherwise reject them as unknown attributes. `#[caller]` and
+/// `#[default]` mark a parameter of a method, `#[state]` marks the `pub use` of a struct
+/// declared outside of the module, and all others mark the method itself.
+pub const HELPER_ATTRIBUTES: &[&str] = &[
+    "caller",
+    "contract_skip",
+    "default",
+    "export",
+    "feeder",
+    "init",
+    "only_owner",
+    "payable",
+    "state",
+    "uses_state",
+    "validate",
+    "view",
+];
+
+/// The components of a contract module, as extracted by [`parse_contract`].
+pub struct Contract {
+    /// The name of the public struct holding the contract's state, or `None` for a module
+    /// without a public struct with the `no_state` option.
+    pub struct_name: Option<Ident>,
+    /// The generic parameters of the public struct.
+    pub generics: Generics,
+    /// The visibility of the public struct.
+    pub visibility: Visibility,
+    /// The `impl` blocks of the public struct, without the inlined `new` method.
+    pub impl_blocks: Vec<ItemImpl>,
+    /// The `new` function of the public struct, if found.
+    pub constructor: Option<Constructor>,
+    /// The public free functions declared in the module.
+    pub functions: Vec<ItemFn>,
+    /// The symbols exported by the `no_mangle` functions declared in the module.
+    pub symbols: Vec<Ident>,
+}
+
+/// The constructor of the contract state, i.e. the `new` function of the public struct.
+pub struct Constructor {
+    /// The `new` function as declared in the contract.
+    pub function: ImplItemFn,
+    /// The strategy used to initialize the contract state with the constructor.
+    pub init: StateInit,
+}
+
+/// The strategy used to initialize the contract state.
+pub enum StateInit {
+    /// The constructor body is inlined into the `static mut STATE` initializer.
+    Inline(Expr),
+    /// The state is assigned at runtime by the generated `init` export, which calls the
+    /// constructor.
+    Runtime,
+}
 
 /// Parses the contract module to extract the public struct, impl blocks, and `new` function.
 ///
-/// This function performs three key tasks:
+/// This function performs four key tasks:
 /// 1. Identifies the public struct in the module.
-/// 2. Collects all implementation blocks and filters out the `new` function.
-/// 3. Extracts the body of the `new` function for state initialization.
+/// 2. Collects all implementation blocks of the public struct and filters out the `new`
+///    function.
+/// 3. Extracts the `new` function and decides how it initializes the state.
+/// 4. Collects the public free functions of the module, except for a free `new`.
+///
+/// The methods of the struct may be spread over several `impl` blocks, all of which
+/// contribute exported methods.
+///
+/// Implementation blocks written for a type alias of the public struct declared in the
+/// same module (e.g. `type Alias = MyStruct; impl Alias { .. }`) are treated as
+/// implementation blocks of the struct itself. Implementation blocks of any other type
+/// are left untouched and are not exported.
+///
+/// [`HELPER_ATTRIBUTES`] are kept on the returned `impl` blocks and functions for the
+/// code generation, but stripped from the module, since the compiler doesn't know them.
 ///
 /// # Parameters
 /// - `input_mod`: The mutable module to parse.
+/// - `options`: The options passed to the `#[contract]` attribute.
 ///
 /// # Returns
-/// - The name of the public struct.
-/// - A vector of `impl` blocks with the `new` method removed.
-/// - The body of the `new` function as an `Expr`.
+/// The [`Contract`] components of the module.
 ///
 /// # Errors
-/// - If there is no public struct.
+/// - If the module has no body, e.g. `pub mod counter;`.
+/// - If there is no public struct, unless the `no_state` option is set.
 /// - If there is more than one public struct.
-pub fn parse_contract(
-    input_mod: &mut ItemMod,
-) -> Result<(Ident, Vec<ItemImpl>, Option<Expr>), TokenStream> {
+/// - If a `use` marked `#[state]` doesn't import a single struct.
+/// - If an exported method takes `self` or uses the state, with the `no_state` option.
+/// - If more than one `impl` block of the struct defines a `new` function.
+/// - If the constructor takes `self`.
+/// - If the struct has no field named by the `owner_field` option.
+/// - If the `where` clause of an `impl` block references an undeclared lifetime.
+/// - If an `impl` block invokes a macro in place of its items.
+/// - If a trait implementation of the struct has generic parameters.
+/// - If the module declares an item named `STATE` other than a `static mut`, which
+///   collides with the generated state.
+/// - If the module invokes a macro in place of items, with the `strict` option.
+/// - If the struct has no method named by the `pre` option, see [`validate_pre_hook`].
+pub fn parse_contract(input_mod: &mut ItemMod, options: &ContractOptions) -> syn::Result<Contract> {
     let mut public_struct = None;
+    let mut generics = Generics::default();
+    let mut visibility = Visibility::Inherited;
+    let mut type_aliases = BTreeMap::new();
     let mut impl_blocks = Vec::new();
-    let mut new_function_body = None;
+    let mut constructor = None;
+    let mut functions = Vec::new();
+    let mut symbols = Vec::new();
 
-    // Parse items in the module
-    if let Some((_, items)) = &mut input_mod.content {
-        for item in items.iter_mut() {
-            match item {
-                Item::Struct(s) => {
-                    handle_public_struct(&mut public_struct, s)?;
-                }
-                Item::Impl(imp) => {
-                    let (filtered_impl, new_body) = process_impl_block(imp)?;
-                    impl_blocks.push(filtered_impl);
-                    if new_function_body.is_none() {
-                        new_function_body = new_body;
+    let items = match &mut input_mod.content {
+        Some((_, items)) => items,
+        None => {
+            return Err(syn::Error::new_spanned(
+                &*input_mod,
+                "`#[contract]` must be applied to an inline module. Move the contents of the module into a `mod name { ... }` block.",
+            ));
+        }
+    };
+
+    // Parse items in the module, finding the public struct before processing its `impl`
+    // blocks below, which may be declared before it
+    for item in items.iter() {
+        match item {
+            Item::Struct(s) => {
+                if handle_public_struct(&mut public_struct, s, options.allow_restricted_state)? {
+                    generics = s.generics.clone();
+                    visibility = s.vis.clone();
+                    if let Some(owner_field) = &options.owner_field {
+                        validate_owner_field(s, owner_field)?;
                     }
                 }
-                _ => {} // Ignore other items
             }
+            Item::Use(item) if item.attrs.iter().any(|attr| attr.path().is_ident("state")) => {
+                handle_state_use(&mut public_struct, item)?;
+                visibility = item.vis.clone();
+            }
+            Item::Type(alias) => {
+                if let Some(target) = type_ident(&alias.ty) {
+                    type_aliases.insert(alias.ident.clone(), target.clone());
+                }
+            }
+            Item::Fn(func) => {
+                symbols.extend(user_symbol(func));
+                if matches!(func.vis, Visibility::Public(_)) && func.sig.ident != "new" {
+                    functions.push(func.clone());
+                }
+            }
+            _ => {} // Ignore other items
+        }
+        reject_state_item(item)?;
+        if options.strict {
+            reject_module_macro(item)?;
+        }
+    }
+
+    // A stateless contract may only export free functions
+    if public_struct.is_none() && !options.no_state {
+        let reexports = items.iter().any(
+            |item| matches!(item, Item::Use(item) if matches!(item.vis, Visibility::Public(_))),
+        );
+        let message = match reexports {
+            true => "A contract module must define exactly one public struct that serves as the contract's state. A struct re-exported with `pub use` isn't seen as the state unless the `use` is marked `#[state]`, e.g. `#[state] pub use inner::MyStruct;`.",
+            false => "A contract module must define exactly one public struct that serves as the contract's state.",
+        };
+        return Err(syn::Error::new_spanned(&input_mod.ident, message));
+    }
+    let struct_name = public_struct;
+    let is_struct_impl = |imp: &ItemImpl| {
+        struct_name
+            .as_ref()
+            .is_some_and(|name| is_contract_impl(imp, name, &type_aliases))
+    };
+
+    // An explicit `#[init]` function takes precedence over `new`
+    let explicit_init = items.iter().any(
+        |item| matches!(item, Item::Impl(imp) if is_struct_impl(imp) && has_init_function(imp)),
+    );
+
+    for item in items.iter_mut() {
+        match item {
+            Item::Impl(imp) if is_struct_impl(imp) => {
+                validate_where_clause(imp)?;
+                reject_item_macros(imp)?;
+                reject_generic_trait_impl(imp)?;
+                let (filtered_impl, constructors) =
+                    process_impl_block(imp, options, explicit_init)?;
+                impl_blocks.push(filtered_impl);
+                for new_function in constructors {
+                    handle_constructor(&mut constructor, new_function)?;
+                }
+            }
+            Item::Fn(func) => func.attrs.retain(|attr| !is_helper_attribute(attr)),
+            Item::Use(item) => item.attrs.retain(|attr| !is_helper_attribute(attr)),
+            _ => {}
         }
     }
 
-    // Unwrap the struct name because `handle_public_struct` ensures it exists
-    let struct_name = public_struct.unwrap();
+    if let Some(hook) = &options.pre {
+        validate_pre_hook(&impl_blocks, hook)?;
+    }
+    if options.no_state {
+        reject_stateful_methods(&impl_blocks)?;
+    }
+
+    Ok(Contract {
+        struct_name,
+        generics,
+        visibility,
+        impl_blocks,
+        constructor,
+        functions,
+        symbols,
+    })
+}
+
+/// Parses an `impl` block annotated with `#[contract]`.
+///
+/// The state of the contract is declared by the user, so the block is processed as is:
+/// its `new` method, if any, isn't interpreted as the constructor of the state.
+///
+/// # Parameters
+/// - `input_impl`: The mutable `impl` block to parse. Its [`HELPER_ATTRIBUTES`] are
+///   stripped.
+///
+/// # Returns
+/// The name of the type the block is implemented for, and the block as seen by the code
+/// generation.
+///
+/// # Errors
+/// - If the type of the block isn't named by an identifier.
+/// - If the `where` clause of the block references an undeclared lifetime.
+/// - If the block invokes a macro in place of its items.
+/// - If the block implements a trait and has generic parameters.
+/// - If the block has no method named by the `pre` option, see [`validate_pre_hook`].
+pub fn parse_contract_impl(
+    input_impl: &mut ItemImpl,
+    options: &ContractOptions,
+) -> syn::Result<(Ident, ItemImpl)> {
+    let struct_name = match self_type_ident(&input_impl.self_ty) {
+        Some(ident) => ident.clone(),
+        None => {
+            return Err(syn::Error::new_spanned(
+                &input_impl.self_ty,
+                "`#[contract]` can only be applied to an `impl` block of a type named by an identifier, such as `impl Counter`.",
+            ));
+        }
+    };
+
+    validate_where_clause(input_impl)?;
+    reject_item_macros(input_impl)?;
+    reject_generic_trait_impl(input_impl)?;
 
-    Ok((struct_name, impl_blocks, new_function_body))
+    // The state is declared by the user, so the constructor isn't exported
+    let explicit_init = has_init_function(input_impl);
+    let mut impl_block = input_impl.clone();
+    impl_block
+        .items
+        .retain(|item| !matches!(item, ImplItem::Fn(func) if is_constructor(func, explicit_init)));
+    if let Some(hook) = &options.pre {
+        validate_pre_hook(core::slice::from_ref(&impl_block), hook)?;
+    }
+    strip_helper_attributes(input_impl);
+    Ok((struct_name, impl_block))
 }
 
 /// Handles the identification of the public struct.
 ///
-/// Ensures that only one public struct is allowed in the module.
+/// Ensures that only one public struct is allowed in the module. The error for a second
+/// public struct points to both structs.
+///
+/// With the `allow_restricted_state` option, a struct with a restricted visibility, e.g.
+/// `pub(crate)`, is treated as public.
 ///
 /// # Parameters
 /// - `public_struct`: Option to store the struct name.
 /// - `struct_item`: The struct item to process.
+/// - `allow_restricted`: Whether a restricted visibility makes the struct public.
+///
+/// # Returns
+/// Whether the struct is public, i.e. is the contract's state.
 ///
 /// # Errors
 /// - If more than one public struct is found.
 fn handle_public_struct(
     public_struct: &mut Option<Ident>,
-    struct_item: &syn::ItemStruct,
-) -> Result<(), TokenStream> {
-    if matches!(struct_item.vis, Visibility::Public(_)) {
-        if public_struct.is_some() {
-            return Err(syn::Error::new_spanned(
-                struct_item,
-                "Only one public struct is allowed in a contract module. Ensure your module defines exactly one public struct that serves as the contract's state.",
-            )
-            .to_compile_error()
-            .into());
+    struct_item: &ItemStruct,
+    allow_restricted: bool,
+) -> syn::Result<bool> {
+    let is_public = match struct_item.vis {
+        Visibility::Public(_) => true,
+        Visibility::Restricted(_) => allow_restricted,
+        Visibility::Inherited => false,
+    };
+    if is_public {
+        if let Some(first) = public_struct {
+            return Err(duplicate_state_error(struct_item, first));
         }
         *public_struct = Some(struct_item.ident.clone());
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Handles a `use` marked `#[state]`, designating a struct declared outside of the module
+/// as the contract's state, e.g. `#[state] pub use inner::MyStruct;`.
+///
+/// The struct is named by the last segment of the path, or by its rename with `as`. As
+/// its declaration isn't seen by the macro, it can't have generic parameters.
+///
+/// # Errors
+/// - If the `use` doesn't import a single item, e.g. `pub use inner::*;`.
+/// - If a public struct is already found.
+fn handle_state_use(public_struct: &mut Option<Ident>, use_item: &ItemUse) -> syn::Result<()> {
+    let mut tree = &use_item.tree;
+    let ident = loop {
+        match tree {
+            UseTree::Path(path) => tree = &path.tree,
+            UseTree::Name(name) => break &name.ident,
+            UseTree::Rename(rename) => break &rename.rename,
+            UseTree::Glob(_) | UseTree::Group(_) => {
+                return Err(syn::Error::new_spanned(
+                    tree,
+                    "`#[state]` must mark the `use` of a single struct, e.g. `#[state] pub use inner::MyStruct;`.",
+                ));
+            }
+        }
+    };
+    if let Some(first) = public_struct {
+        return Err(duplicate_state_error(use_item, first));
+    }
+    *public_struct = Some(ident.clone());
+    Ok(())
+}
+
+/// Returns the error for a second public struct, spanned on it and on the first one.
+fn duplicate_state_error(second: impl ToTokens, first: &Ident) -> syn::Error {
+    let mut error = syn::Error::new_spanned(
+        second,
+        format!("Only one public struct is allowed in a contract module, but `{first}` is already public. Ensure your module defines exactly one public struct that serves as the contract's state."),
+    );
+    error.combine(syn::Error::new_spanned(
+        first,
+        format!("the public struct `{first}` is defined here"),
+    ));
+    error
+}
+
+/// Rejects an item of the module named `STATE`, as `#[contract]` declares the state of
+/// the contract under that name.
+///
+/// A `static mut STATE` is accepted as the state of the contract declared by the user, in
+/// place of the generated one, see [`generate_state_declaration`].
+///
+/// [`generate_state_declaration`]: crate::contract::state::generate_state_declaration
+///
+/// # Errors
+/// If the item is named `STATE`, and isn't a `static mut`.
+fn reject_state_item(item: &Item) -> syn::Result<()> {
+    let ident = match item {
+        Item::Static(item) if matches!(item.mutability, StaticMutability::Mut(_)) => return Ok(()),
+        Item::Static(item) => &item.ident,
+        Item::Const(item) => &item.ident,
+        Item::Fn(item) => &item.sig.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        _ => return Ok(()),
+    };
+    if ident == "STATE" {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "The contract module can't declare an item named `STATE`, as `#[contract]` generates the contract state under that name. Rename the item, or declare the contract state yourself as a `static mut STATE`.",
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the symbol exported by a function marked `#[no_mangle]` or
+/// `#[export_name = "..."]`, if any.
+fn user_symbol(func: &ItemFn) -> Option<Ident> {
+    func.attrs.iter().find_map(|attr| {
+        if attr.path().is_ident("no_mangle") {
+            return Some(func.sig.ident.clone());
+        }
+        match &attr.meta {
+            Meta::NameValue(meta) if meta.path.is_ident("export_name") => match &meta.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(name),
+                    ..
+                }) => name.parse().ok(),
+                _ => None,
+            },
+            _ => None,
+        }
+    })
+}
+
+/// Validates that the field named by the `owner_field` option exists on the state struct.
+///
+/// # Errors
+/// - If the struct has no field with the given name.
+fn validate_owner_field(struct_item: &ItemStruct, owner_field: &Ident) -> syn::Result<()> {
+    let exists = struct_item
+        .fields
+        .iter()
+        .any(|field| field.ident.as_ref() == Some(owner_field));
+    if !exists {
+        return Err(syn::Error::new_spanned(
+            owner_field,
+            format!(
+                "The contract's state `{}` has no field named `{owner_field}` to hold the owner.",
+                struct_item.ident
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects the exported methods that access the contract state with the `no_state`
+/// option, i.e. that take `self` or are marked `#[uses_state]`, as no state is declared.
+///
+/// # Errors
+/// If an exported method accesses the state. The error is spanned on its receiver or
+/// attribute.
+fn reject_stateful_methods(impl_blocks: &[ItemImpl]) -> syn::Result<()> {
+    for method in impl_blocks.iter().flat_map(exported_methods) {
+        let uses_state = method
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("uses_state"));
+        let spanned = match (method.sig.receiver(), uses_state) {
+            (Some(receiver), _) => receiver.to_token_stream(),
+            (None, Some(attr)) => attr.to_token_stream(),
+            (None, None) => continue,
+        };
+        return Err(syn::Error::new_spanned(
+            spanned,
+            format!(
+                "The method `{}` accesses the contract state, which the `no_state` option doesn't declare. Only associated functions without `self` are exported by stateless contracts.",
+                method.sig.ident
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates that the `impl` blocks of the contract's state declare the hook named by the
+/// `pre` option, as a method taking `&self`.
+///
+/// # Errors
+/// - If no block declares a function with the name of the hook.
+/// - If the hook doesn't take `&self`, or takes other arguments.
+fn validate_pre_hook(impl_blocks: &[ItemImpl], hook: &Ident) -> syn::Result<()> {
+    let function = impl_blocks
+        .iter()
+        .flat_map(|imp| &imp.items)
+        .find_map(|item| match item {
+            ImplItem::Fn(func) if func.sig.ident == *hook => Some(func),
+            _ => None,
+        });
+    let function = match function {
+        Some(function) => function,
+        None => {
+            return Err(syn::Error::new_spanned(
+                hook,
+                format!("The contract's state has no method `{hook}` to run before the calls, as named by the `pre` option."),
+            ));
+        }
+    };
+
+    let takes_ref_self = matches!(
+        function.sig.receiver(),
+        Some(receiver) if receiver.reference.is_some() && receiver.mutability.is_none()
+    );
+    if !takes_ref_self || function.sig.inputs.len() != 1 {
+        return Err(syn::Error::new_spanned(
+            &function.sig,
+            format!("The `pre` hook `{hook}` must only take `&self`, e.g. `fn {hook}(&self) -> Result<(), Error>`."),
+        ));
+    }
+    Ok(())
+}
+
+/// Validates the `where` clause of an `impl` block of the contract's state.
+///
+/// The clause is preserved on the emitted block, and bounds such as `where Self: Sized`
+/// are accepted. A bound referencing a lifetime that isn't declared on the block is
+/// rejected, since the exported wrappers have no way to name it.
+///
+/// # Errors
+/// - If the `where` clause references an undeclared lifetime.
+fn validate_where_clause(impl_block: &ItemImpl) -> syn::Result<()> {
+    let mut where_clause = match &impl_block.generics.where_clause {
+        Some(where_clause) => where_clause.clone(),
+        None => return Ok(()),
+    };
+
+    let mut visitor = UndeclaredLifetime {
+        declared: impl_block
+            .generics
+            .lifetimes()
+            .map(|param| param.lifetime.clone())
+            .collect(),
+        found: None,
+    };
+    visitor.visit_where_clause_mut(&mut where_clause);
+
+    match visitor.found {
+        Some(lifetime) => Err(syn::Error::new_spanned(
+            &lifetime,
+            format!("The `where` clause of the `impl` block references the undeclared lifetime `{lifetime}`. Declare it on the block, e.g. `impl<{lifetime}> ...`."),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rejects macro invocations among the items of an `impl` block of the contract's state.
+///
+/// `#[contract]` expands before the macros invoked in the block, so the methods they
+/// generate would silently not be exported.
+///
+/// # Errors
+/// If the block contains a macro invocation, e.g. `getters!(value);`.
+fn reject_item_macros(impl_block: &ItemImpl) -> syn::Result<()> {
+    match impl_block.items.iter().find_map(|item| match item {
+        ImplItem::Macro(item_macro) => Some(item_macro),
+        _ => None,
+    }) {
+        Some(item_macro) => Err(syn::Error::new_spanned(
+            item_macro,
+            "Methods generated by a macro in an `impl` block of the contract's state aren't exported, as `#[contract]` expands before the macro. Write the methods out in the `impl` block instead.",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rejects a trait implementation of the contract's state with generic parameters, e.g.
+/// `impl<T: Into<u64>> Convert<T> for Counter`.
+///
+/// Each exported method is a single function of the contract, so it can't be generic over
+/// the parameters of its `impl` block.
+///
+/// # Errors
+/// If the block implements a trait and declares generic parameters. The error is spanned
+/// on the parameters.
+fn reject_generic_trait_impl(impl_block: &ItemImpl) -> syn::Result<()> {
+    if impl_block.trait_.is_some() && !impl_block.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &impl_block.generics.params,
+            "The methods of a generic trait implementation can't be exported, as each exported method must be a single function of the contract. Implement the trait for concrete types instead, e.g. `impl Convert<u32> for Counter`.",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects a macro invocation in place of items of the module, with the `strict` option.
+///
+/// `#[contract]` expands before the macro, so a public struct or an `impl` block it
+/// generates would silently not be part of the contract.
+///
+/// # Errors
+/// If the item is a macro invocation, e.g. `impl_state!(Counter);`.
+fn reject_module_macro(item: &Item) -> syn::Result<()> {
+    match item {
+        Item::Macro(item_macro) => Err(syn::Error::new_spanned(
+            item_macro,
+            "Items generated by a macro in the contract module aren't seen by `#[contract]`, as it expands before the macro. Write the items out in the module, or remove the `strict` option.",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Finds the first lifetime that isn't declared on an `impl` block.
+struct UndeclaredLifetime {
+    declared: Vec<Lifetime>,
+    found: Option<Lifetime>,
+}
+
+impl VisitMut for UndeclaredLifetime {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        let is_declared =
+            lifetime.ident == "static" || lifetime.ident == "_" || self.declared.contains(lifetime);
+        if !is_declared && self.found.is_none() {
+            self.found = Some(lifetime.clone());
+        }
+    }
+
+    fn visit_predicate_type_mut(&mut self, predicate: &mut syn::PredicateType) {
+        let declared = self.declare_higher_ranked(predicate.lifetimes.as_ref());
+        syn::visit_mut::visit_predicate_type_mut(self, predicate);
+        self.declared.truncate(declared);
+    }
+
+    fn visit_trait_bound_mut(&mut self, bound: &mut syn::TraitBound) {
+        let declared = self.declare_higher_ranked(bound.lifetimes.as_ref());
+        syn::visit_mut::visit_trait_bound_mut(self, bound);
+        self.declared.truncate(declared);
+    }
+}
+
+impl UndeclaredLifetime {
+    /// Declares the higher-ranked lifetimes of a bound, e.g. `for<'a>`, returning the
+    /// number of lifetimes declared before them.
+    fn declare_higher_ranked(&mut self, lifetimes: Option<&syn::BoundLifetimes>) -> usize {
+        let declared = self.declared.len();
+        if let Some(lifetimes) = lifetimes {
+            self.declared
+                .extend(lifetimes.lifetimes.iter().filter_map(|param| match param {
+                    syn::GenericParam::Lifetime(param) => Some(param.lifetime.clone()),
+                    _ => None,
+                }));
+        }
+        declared
+    }
+}
+
+/// Handles the identification of the constructor.
+///
+/// Ensures that only one constructor, either marked `#[init]` or named `new`, is defined
+/// across the `impl` blocks of the public struct.
+///
+/// # Parameters
+/// - `constructor`: Option to store the constructor.
+/// - `new_function`: The constructor found in an `impl` block.
+///
+/// # Errors
+/// - If a constructor was already found.
+fn handle_constructor(
+    constructor: &mut Option<Constructor>,
+    new_function: Constructor,
+) -> syn::Result<()> {
+    if let Some(existing) = constructor {
+        let (message, note) = if existing.function.sig.ident == "new" {
+            (
+                "Only one `new` function is allowed across the `impl` blocks of the contract's state.",
+                "the first `new` function is defined here",
+            )
+        } else {
+            (
+                "Only one function can be marked `#[init]` across the `impl` blocks of the contract's state.",
+                "the first `#[init]` function is defined here",
+            )
+        };
+        let mut error = syn::Error::new_spanned(&new_function.function.sig, message);
+        error.combine(syn::Error::new_spanned(&existing.function.sig, note));
+        return Err(error);
     }
+    *constructor = Some(new_function);
     Ok(())
 }
 
-/// Processes an `impl` block to filter out the `new` function and collect its body.
+/// Returns the identifier of a type consisting of a single, non-generic path segment.
+fn type_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => type_path.path.get_ident(),
+        _ => None,
+    }
+}
+
+/// Returns the identifier of the type an `impl` block is written for, consisting of a
+/// single path segment, e.g. `MyStruct` for both `impl MyStruct` and `impl MyStruct<u32>`.
+fn self_type_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(type_path)
+            if type_path.qself.is_none()
+                && type_path.path.leading_colon.is_none()
+                && type_path.path.segments.len() == 1 =>
+        {
+            Some(&type_path.path.segments[0].ident)
+        }
+        _ => None,
+    }
+}
+
+/// Checks whether an `impl` block belongs to the contract's public struct.
+///
+/// The self type of the block matches if it is the struct itself, or a type alias that
+/// (possibly through other aliases) resolves to the struct.
+///
+/// Blocks marked `#[automatically_derived]`, such as the `impl Clone` generated by
+/// `#[derive(Clone)]` when the derive runs before `#[contract]`, are never contract
+/// blocks: their methods aren't part of the contract's interface. Neither is an
+/// `impl Drop` block, whose `drop` can't be called explicitly, and which never runs for
+/// the state, as a `static` isn't dropped.
+///
+/// As the methods of a trait implementation can't be `pub`, all of them would be
+/// exported, so a trait implementation must opt in by being marked `#[export]`. Other
+/// trait implementations, such as a manual `impl Debug` or the implementation of a helper
+/// trait declared in the module, are left as they are. Inherent blocks with only
+/// private methods don't need to be skipped, as they export nothing, and may hold the
+/// constructor or the `pre` hook.
+///
+/// # Parameters
+/// - `impl_block`: The implementation block to check.
+/// - `struct_name`: The name of the public struct.
+/// - `type_aliases`: The simple type aliases declared in the module.
+fn is_contract_impl(
+    impl_block: &ItemImpl,
+    struct_name: &Ident,
+    type_aliases: &BTreeMap<Ident, Ident>,
+) -> bool {
+    let is_derived = impl_block
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("automatically_derived"));
+    let is_drop = matches!(&impl_block.trait_, Some((_, path, _)) if path.segments.last().is_some_and(|segment| segment.ident == "Drop"));
+    if is_derived || is_drop {
+        return false;
+    }
+    let is_marked = impl_block
+        .attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("export"));
+    if impl_block.trait_.is_some() && !is_marked {
+        return false;
+    }
+
+    let mut current = match self_type_ident(&impl_block.self_ty) {
+        Some(ident) => ident,
+        None => return false,
+    };
+
+    // Follow alias chains, bounded by the number of aliases to avoid cycles
+    for _ in 0..=type_aliases.len() {
+        if current == struct_name {
+            return true;
+        }
+        match type_aliases.get(current) {
+            Some(target) => current = target,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Processes an `impl` block to filter out the constructor and collect it.
+///
+/// The constructor is the function marked `#[init]` if the contract has one, and the
+/// `new` function otherwise.
 ///
 /// # Parameters
 /// - `impl_block`: The implementation block to process.
+/// - `options`: The options passed to the `#[contract]` attribute.
+/// - `explicit_init`: Whether a function of the contract is marked `#[init]`.
+///
+/// A constructor called at runtime by the `init` export is kept in the block emitted in
+/// the module, while an inlined constructor is removed from it. With the `boxed_state`
+/// option, the state is always initialized at runtime, as it's allocated on the heap.
 ///
 /// # Returns
-/// - The filtered implementation block without the `new` method.
-/// - The body of the `new` function, if found.
-fn process_impl_block(impl_block: &mut ItemImpl) -> Result<(ItemImpl, Option<Expr>), TokenStream> {
+/// - The implementation block seen by the code generation, without the constructor.
+/// - The constructors found, with their state initialization strategy.
+///
+/// # Errors
+/// If a constructor takes `self`.
+fn process_impl_block(
+    impl_block: &mut ItemImpl,
+    options: &ContractOptions,
+    explicit_init: bool,
+) -> syn::Result<(ItemImpl, Vec<Constructor>)> {
     let mut filtered_methods = Vec::new();
-    let mut new_function_body = None;
+    let mut exported_methods = Vec::new();
+    let mut constructors = Vec::new();
 
     for item in &impl_block.items {
         if let ImplItem::Fn(func) = item {
-            // Check if this method is the `new` function`
-            if func.sig.ident == "new" {
-                // Extract the first expression in the `new` function's body
-                if let Some(stmt) = func.block.stmts.first() {
-                    if let syn::Stmt::Expr(expr, _) = stmt {
-                        new_function_body = Some(expr.clone());
-                    }
+            if !options.no_state && is_constructor(func, explicit_init) {
+                reject_constructor_receiver(func)?;
+                let init = if options.boxed_state {
+                    StateInit::Runtime
+                } else {
+                    state_init(func)
+                };
+                let is_inline = matches!(init, StateInit::Inline(_));
+                constructors.push(Constructor {
+                    function: func.clone(),
+                    init,
+                });
+                if !is_inline {
+                    filtered_methods.push(item.clone());
                 }
-                continue; // Skip adding `new` to filtered methods
+                continue;
             }
         }
-        // Add all other methods to the filtered list
+        // Add all other methods to the filtered lists
         filtered_methods.push(item.clone());
+        exported_methods.push(item.clone());
     }
 
+    let mut exported_impl = impl_block.clone();
+    exported_impl.items = exported_methods;
     impl_block.items = filtered_methods;
-    Ok((impl_block.clone(), new_function_body))
+    strip_helper_attributes(impl_block);
+    Ok((exported_impl, constructors))
+}
+
+/// Rejects a constructor taking `self`, e.g. `pub fn new(&self) -> Self`, as there is no
+/// state to call it on before it is created.
+///
+/// # Errors
+/// If the constructor has a receiver. The error is spanned on the receiver.
+fn reject_constructor_receiver(func: &ImplItemFn) -> syn::Result<()> {
+    match func.sig.receiver() {
+        Some(receiver) => Err(syn::Error::new_spanned(
+            receiver,
+            format!(
+                "The constructor `{}` of the contract state must not take `self`, as it creates the state. Remove the receiver, e.g. `pub fn {0}() -> Self`.",
+                func.sig.ident
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Checks whether a function is the constructor of the contract state.
+///
+/// # Parameters
+/// - `func`: The function to check.
+/// - `explicit_init`: Whether a function of the contract is marked `#[init]`, in which
+///   case `new` is an ordinary function.
+fn is_constructor(func: &ImplItemFn, explicit_init: bool) -> bool {
+    if explicit_init {
+        func.attrs.iter().any(|attr| attr.path().is_ident("init"))
+    } else {
+        func.sig.ident == "new"
+    }
+}
+
+/// Checks whether an `impl` block contains a function marked `#[init]`.
+fn has_init_function(impl_block: &ItemImpl) -> bool {
+    impl_block
+        .items
+        .iter()
+        .any(|item| matches!(item, ImplItem::Fn(func) if is_constructor(func, true)))
+}
+
+/// Removes the helper attributes listed in [`HELPER_ATTRIBUTES`] from an `impl` block,
+/// its methods and their parameters.
+fn strip_helper_attributes(impl_block: &mut ItemImpl) {
+    impl_block.attrs.retain(|attr| !is_helper_attribute(attr));
+    for item in &mut impl_block.items {
+        if let ImplItem::Fn(func) = item {
+            func.attrs.retain(|attr| !is_helper_attribute(attr));
+            for input in &mut func.sig.inputs {
+                if let FnArg::Typed(pat_type) = input {
+                    pat_type.attrs.retain(|attr| !is_helper_attribute(attr));
+                }
+            }
+        }
+    }
+}
+
+/// Checks whether an attribute is one of the [`HELPER_ATTRIBUTES`] of the macro.
+pub fn is_helper_attribute(attr: &Attribute) -> bool {
+    HELPER_ATTRIBUTES
+        .iter()
+        .any(|helper| attr.path().is_ident(helper))
+}
+
+/// Decides how the `new` function initializes the contract state.
+///
+/// The body of the constructor is inlined into the `static mut STATE` initializer when
+/// it can be evaluated at compile time:
+/// - A `const fn new` is inlined, as its whole body is const-evaluable.
+/// - A non-const `new` consisting of a single expression is inlined, unless the
+///   expression is a call, e.g. `Self::with_supply(1000)` or a builder chain such as
+///   `CounterBuilder::new().build()`, since the called functions may not be `const`.
+///   The same applies to a struct expression whose base is a call, such as
+///   `Self { value: 0, ..Default::default() }`, to an `if` or `match` picking the
+///   initial state, whose conditions and arms may call such functions, and to a macro
+///   invocation such as `my_init!()`, whose expansion the macro can't see.
+///
+/// The expression of a constructor may be returned with `return` or wrapped in a block.
+/// Any other `return`, such as an early `return Self { .. };` in an `if`, can't be part
+/// of the initializer, so the constructor is called at runtime instead, see
+/// [`ReturnFinder`].
+///
+/// A constructor taking arguments, or a non-const constructor with statements preceding
+/// its final expression or delegating to another function, is called at runtime instead.
+fn state_init(func: &ImplItemFn) -> StateInit {
+    if !func.sig.inputs.is_empty() {
+        return StateInit::Runtime;
+    }
+
+    let mut expr = match sole_expression(&func.block) {
+        Some(expr) if func.sig.constness.is_some() || !needs_runtime_init(expr) => expr.clone(),
+        _ if func.sig.constness.is_some() => {
+            let mut block = func.block.clone();
+            // A trailing `return` would return from the initializer of `STATE`
+            if let Some(Stmt::Expr(Expr::Return(ret), _)) = block.stmts.last() {
+                if let Some(expr) = &ret.expr {
+                    let tail = Stmt::Expr((**expr).clone(), None);
+                    *block.stmts.last_mut().unwrap() = tail;
+                }
+            }
+            Expr::Block(syn::ExprBlock {
+                attrs: Vec::new(),
+                label: None,
+                block,
+            })
+        }
+        _ => return StateInit::Runtime,
+    };
+
+    let mut finder = ReturnFinder { found: false };
+    finder.visit_expr_mut(&mut expr);
+    match finder.found {
+        true => StateInit::Runtime,
+        false => StateInit::Inline(expr),
+    }
+}
+
+/// Finds a `return` expression in the initializer inlined from a constructor, which
+/// would return from the initializer of `STATE`.
+///
+/// The `return` expressions of closures and of items declared in the constructor return
+/// from these, and are ignored.
+struct ReturnFinder {
+    found: bool,
+}
+
+impl VisitMut for ReturnFinder {
+    fn visit_expr_return_mut(&mut self, _: &mut syn::ExprReturn) {
+        self.found = true;
+    }
+
+    fn visit_expr_closure_mut(&mut self, _: &mut syn::ExprClosure) {}
+
+    fn visit_item_mut(&mut self, _: &mut Item) {}
+}
+
+/// Returns the expression a block consisting of a single expression evaluates to.
+///
+/// The expression is unwrapped from a `return`, e.g. `return Self { .. };`, and from
+/// nested blocks, e.g. `{ Self { .. } }`.
+fn sole_expression(block: &Block) -> Option<&Expr> {
+    let expr = match block.stmts.as_slice() {
+        [Stmt::Expr(expr, None)] => expr,
+        [Stmt::Expr(Expr::Return(ret), _)] => ret.expr.as_deref()?,
+        _ => return None,
+    };
+    match expr {
+        Expr::Return(ret) => ret.expr.as_deref(),
+        Expr::Block(inner) if inner.attrs.is_empty() && inner.label.is_none() => {
+            sole_expression(&inner.block)
+        }
+        _ => Some(expr),
+    }
+}
+
+/// Checks whether an expression is a function or method call, or a struct expression
+/// whose base, e.g. `..Default::default()`, is one.
+fn is_delegating(expr: &Expr) -> bool {
+    match expr {
+        Expr::Call(_) | Expr::MethodCall(_) => true,
+        Expr::Struct(expr) => expr.rest.as_deref().is_some_and(is_delegating),
+        _ => false,
+    }
+}
+
+/// Checks whether the sole expression of a non-const constructor may not be evaluable at
+/// compile time: a call, see [`is_delegating`], an `if` or a `match`, or a macro
+/// invocation.
+fn needs_runtime_init(expr: &Expr) -> bool {
+    is_delegating(expr) || matches!(expr, Expr::If(_) | Expr::Match(_) | Expr::Macro(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_contract, StateInit};
+    use crate::contract::functions::exported_methods;
+    use crate::contract::options::ContractOptions;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use quote::quote;
+    use syn::{parse_quote, Expr, ImplItem, Item, ItemImpl, ItemMod};
+
+    #[test]
+    fn test_impl_on_type_alias_is_contract_impl() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                type Alias = Counter;
+
+                impl Alias {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.struct_name.unwrap(), "Counter");
+        assert_eq!(contract.impl_blocks.len(), 1);
+        assert_eq!(contract.impl_blocks[0].items.len(), 1);
+        assert!(contract.constructor.is_some());
+    }
+
+    #[test]
+    fn test_public_free_functions_are_collected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod hasher {
+                pub struct Hasher;
+
+                impl Hasher {
+                    pub const fn new() -> Self {
+                        Self
+                    }
+                }
+
+                pub fn hash(data: Vec<u8>) -> [u8; 32] {
+                    digest(&data)
+                }
+
+                fn digest(data: &[u8]) -> [u8; 32] {
+                    [0; 32]
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.functions.len(), 1);
+        assert_eq!(contract.functions[0].sig.ident, "hash");
+    }
+
+    #[test]
+    fn test_owner_field_must_exist() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+        let options = ContractOptions {
+            owner_field: Some(parse_quote!(owner)),
+            ..ContractOptions::default()
+        };
+
+        let error = parse_contract(&mut input_mod, &options).err().unwrap();
+
+        assert!(error.to_string().contains("has no field named `owner`"));
+    }
+
+    #[test]
+    fn test_only_owner_is_stripped_from_module() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    owner: ContractId,
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self {
+                            owner: ContractId::from_bytes([0; 32]),
+                            value: 0,
+                        }
+                    }
+
+                    #[only_owner]
+                    pub fn reset(&mut self) {
+                        self.value = 0;
+                    }
+                }
+            }
+        };
+        let options = ContractOptions {
+            owner_field: Some(parse_quote!(owner)),
+            ..ContractOptions::default()
+        };
+
+        let contract = parse_contract(&mut input_mod, &options).unwrap();
+
+        let has_only_owner = |imp: &ItemImpl| match &imp.items[0] {
+            ImplItem::Fn(func) => func.attrs.iter().any(|a| a.path().is_ident("only_owner")),
+            _ => false,
+        };
+        assert!(has_only_owner(&contract.impl_blocks[0]));
+        let emitted = match &input_mod.content.unwrap().1[1] {
+            Item::Impl(imp) => imp.clone(),
+            _ => panic!("expected the impl block"),
+        };
+        assert!(!has_only_owner(&emitted));
+    }
+
+    #[test]
+    fn test_bodiless_module_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter;
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("`#[contract]` must be applied to an inline module"));
+    }
+
+    #[test]
+    fn test_impl_on_other_type_is_not_contract_impl() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                struct Helper;
+
+                impl Helper {
+                    pub fn new() -> Self {
+                        Self
+                    }
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.impl_blocks.len(), 1);
+        let expected: Expr = parse_quote! { Self { value: 0 } };
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(expr) if expr == expected
+        ));
+    }
+
+    #[test]
+    fn test_methods_across_multiple_impl_blocks() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+
+                impl Counter {
+                    pub fn increment(&mut self) {
+                        self.value += 1;
+                    }
+                }
+
+                impl Counter {
+                    pub fn decrement(&mut self) {
+                        self.value -= 1;
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.impl_blocks.len(), 3);
+        let exported: Vec<_> = contract
+            .impl_blocks
+            .iter()
+            .flat_map(exported_methods)
+            .map(|method| method.sig.ident.to_string())
+            .collect();
+        assert_eq!(exported, ["read_value", "increment", "decrement"]);
+        assert!(contract.constructor.is_some());
+    }
+
+    #[test]
+    fn test_multiple_new_functions_are_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 1 }
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        let messages: Vec<_> = error.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("Only one `new` function is allowed"));
+    }
+
+    #[test]
+    fn test_const_new_is_inlined() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        let value = 42;
+                        Self { value }
+                    }
+                }
+            }
+        };
+
+        let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+            .unwrap()
+            .constructor;
+
+        let expected: Expr = parse_quote! {{
+            let value = 42;
+            Self { value }
+        }};
+        assert!(matches!(
+            constructor.unwrap().init,
+            StateInit::Inline(expr) if expr == expected
+        ));
+    }
+
+    #[test]
+    fn test_branching_new_uses_runtime_init() {
+        for body in [
+            quote! {
+                if cfg!(feature = "testnet") {
+                    Self { value: 1 }
+                } else {
+                    Self { value: initial_value() }
+                }
+            },
+            quote! {
+                match network() {
+                    Network::Testnet => Self { value: 1 },
+                    _ => Self { value: 0 },
+                }
+            },
+        ] {
+            let mut input_mod: ItemMod = parse_quote! {
+                pub mod counter {
+                    pub struct Counter {
+                        value: i64,
+                    }
+
+                    impl Counter {
+                        pub fn new() -> Self {
+                            #body
+                        }
+                    }
+                }
+            };
+
+            let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+                .unwrap()
+                .constructor;
+
+            assert!(matches!(constructor.unwrap().init, StateInit::Runtime));
+        }
+    }
+
+    #[test]
+    fn test_macro_new_uses_runtime_init() {
+        for body in [
+            quote! { my_init!() },
+            quote! { return my_init!(); },
+            quote! { { my_init!() } },
+        ] {
+            let mut input_mod: ItemMod = parse_quote! {
+                pub mod counter {
+                    pub struct Counter {
+                        value: i64,
+                    }
+
+                    impl Counter {
+                        pub fn new() -> Self {
+                            #body
+                        }
+                    }
+                }
+            };
+
+            let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+                .unwrap()
+                .constructor;
+
+            assert!(matches!(constructor.unwrap().init, StateInit::Runtime));
+        }
+    }
+
+    #[test]
+    fn test_branching_const_new_is_inlined() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        if TESTNET {
+                            Self { value: 1 }
+                        } else {
+                            Self { value: 0 }
+                        }
+                    }
+                }
+            }
+        };
+
+        let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+            .unwrap()
+            .constructor;
+
+        assert!(matches!(
+            constructor.unwrap().init,
+            StateInit::Inline(Expr::If(_))
+        ));
+    }
+
+    #[test]
+    fn test_non_const_new_with_statements_uses_runtime_init() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        let value = compute_initial_value();
+                        Self { value }
+                    }
+                }
+            }
+        };
+
+        let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+            .unwrap()
+            .constructor;
+
+        assert!(matches!(constructor.unwrap().init, StateInit::Runtime));
+    }
+
+    #[test]
+    fn test_new_delegating_to_helper_uses_runtime_init() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    supply: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self::with_supply(1000)
+                    }
+
+                    fn with_supply(supply: u64) -> Self {
+                        Self { supply }
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Runtime
+        ));
+        // The runtime `new` stays in the module to be called lazily, but isn't exported
+        assert_eq!(contract.impl_blocks[0].items.len(), 1);
+        let (_, items) = input_mod.content.unwrap();
+        let module_impl = items
+            .iter()
+            .find_map(|item| match item {
+                Item::Impl(imp) => Some(imp),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(module_impl.items.len(), 2);
+    }
+
+    #[test]
+    fn test_const_new_delegating_to_helper_is_inlined() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    supply: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self::with_supply(1000)
+                    }
+
+                    const fn with_supply(supply: u64) -> Self {
+                        Self { supply }
+                    }
+                }
+            }
+        };
+
+        let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+            .unwrap()
+            .constructor;
+
+        let expected: Expr = parse_quote!(Self::with_supply(1000));
+        assert!(matches!(constructor.unwrap().init, StateInit::Inline(expr) if expr == expected));
+    }
+
+    #[test]
+    fn test_multiple_public_structs_reference_both() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                pub struct Config {
+                    limit: i64,
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+        let messages: Vec<_> = error.into_iter().map(|error| error.to_string()).collect();
+
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("but `Counter` is already public"));
+        assert_eq!(messages[1], "the public struct `Counter` is defined here");
+    }
+
+    #[test]
+    fn test_impl_declared_before_struct_is_exported() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+
+                pub struct Counter {
+                    value: i64,
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.struct_name.unwrap(), "Counter");
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(_)
+        ));
+        let methods: Vec<_> = exported_methods(&contract.impl_blocks[0])
+            .map(|method| method.sig.ident.to_string())
+            .collect();
+        assert_eq!(methods, ["read_value"]);
+    }
+
+    #[test]
+    fn test_reexported_state_is_designated_by_state_attribute() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod vault {
+                #[state]
+                pub use crate::types::Vault as Safe;
+
+                impl Safe {
+                    pub const fn new() -> Self {
+                        Self { balance: 0 }
+                    }
+
+                    pub fn balance(&self) -> u64 {
+                        self.balance
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.struct_name.unwrap(), "Safe");
+        assert_eq!(contract.impl_blocks.len(), 1);
+        let (_, items) = input_mod.content.unwrap();
+        assert!(matches!(&items[0], Item::Use(item) if item.attrs.is_empty()));
+    }
+
+    #[test]
+    fn test_reexported_state_without_attribute_is_explained() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod vault {
+                pub use crate::types::Vault;
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("isn't seen as the state unless the `use` is marked `#[state]`"));
+
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod vault {
+                #[state]
+                pub use crate::types::*;
+            }
+        };
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "`#[state]` must mark the `use` of a single struct, e.g. `#[state] pub use inner::MyStruct;`."
+        );
+    }
+
+    #[test]
+    fn test_derived_impl_is_not_exported() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: i64,
+                }
+
+                #[automatically_derived]
+                impl ::core::clone::Clone for Counter {
+                    #[inline]
+                    fn clone(&self) -> Counter {
+                        Counter {
+                            value: ::core::clone::Clone::clone(&self.value),
+                        }
+                    }
+                }
+
+                impl core::hash::Hash for Counter {
+                    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+                        self.value.hash(state);
+                    }
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> i64 {
+                        self.value
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(contract.impl_blocks.len(), 1);
+        let exported: Vec<_> = contract
+            .impl_blocks
+            .iter()
+            .flat_map(exported_methods)
+            .map(|method| method.sig.ident.to_string())
+            .collect();
+        assert_eq!(exported, ["read_value"]);
+    }
+
+    #[test]
+    fn test_init_attribute_selects_constructor() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    #[init]
+                    pub fn with_value(value: u64) -> Self {
+                        Self { value }
+                    }
+
+                    pub fn read_value(&self) -> u64 {
+                        self.value
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        let constructor = contract.constructor.unwrap();
+        assert_eq!(constructor.function.sig.ident, "with_value");
+        assert!(matches!(constructor.init, StateInit::Runtime));
+        assert_eq!(contract.impl_blocks[0].items.len(), 1);
+    }
+
+    #[test]
+    fn test_init_attribute_takes_precedence_over_new() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+
+                impl Counter {
+                    #[init]
+                    pub fn with_value(value: u64) -> Self {
+                        Self { value }
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert_eq!(
+            contract.constructor.unwrap().function.sig.ident,
+            "with_value"
+        );
+        // `new` is an ordinary static method, exported like any other
+        let ImplItem::Fn(new) = &contract.impl_blocks[0].items[0] else {
+            panic!("expected the `new` function");
+        };
+        assert_eq!(new.sig.ident, "new");
+    }
+
+    #[test]
+    fn test_multiple_init_functions_are_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    #[init]
+                    pub fn with_value(value: u64) -> Self {
+                        Self { value }
+                    }
+
+                    #[init]
+                    pub fn zero() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        let messages: Vec<_> = error.into_iter().map(|e| e.to_string()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("Only one function can be marked `#[init]`"));
+    }
+
+    #[test]
+    fn test_impl_where_clause_is_preserved() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter
+                where
+                    Self: Sized,
+                {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    pub fn read_value(&self) -> u64 {
+                        self.value
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(_)
+        ));
+        let expected: syn::WhereClause = parse_quote! { where Self: Sized, };
+        assert_eq!(
+            contract.impl_blocks[0].generics.where_clause,
+            Some(expected.clone())
+        );
+        let (_, items) = input_mod.content.unwrap();
+        let Item::Impl(module_impl) = &items[1] else {
+            panic!("expected the `impl` block");
+        };
+        assert_eq!(module_impl.generics.where_clause, Some(expected));
+    }
+
+    #[test]
+    fn test_impl_where_clause_with_undeclared_lifetime_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter
+                where
+                    Self: 'a,
+                    for<'b> &'b Self: Sized,
+                {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert!(error.to_string().contains("undeclared lifetime `'a`"));
+    }
+
+    #[test]
+    fn test_impl_where_clause_with_higher_ranked_lifetime_is_accepted() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter
+                where
+                    for<'a> &'a Self: Sized,
+                    Self: 'static,
+                {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        assert!(parse_contract(&mut input_mod, &ContractOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_struct_update_with_call_uses_runtime_init() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                #[derive(Default)]
+                pub struct Counter {
+                    value: u64,
+                    supply: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        Self { value: 0, ..Default::default() }
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Runtime
+        ));
+    }
+
+    #[test]
+    fn test_macro_in_impl_block_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+
+                    getters!(value);
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("Methods generated by a macro in an `impl` block of the contract's state aren't exported"));
+    }
+
+    #[test]
+    fn test_immutable_user_defined_state_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                static STATE: Counter = Counter { value: 0 };
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert!(error
+            .to_string()
+            .contains("The contract module can't declare an item named `STATE`"));
+    }
+
+    #[test]
+    fn test_builder_new_uses_runtime_init() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        CounterBuilder::new().value(5).build()
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Runtime
+        ));
+    }
+
+    #[test]
+    fn test_returned_constructor_expression_is_inlined() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        return Self { value: 0 };
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        let expected: Expr = parse_quote! { Self { value: 0 } };
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(expr) if expr == expected
+        ));
+    }
+
+    #[test]
+    fn test_block_wrapped_constructor_expression_is_inlined() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub fn new() -> Self {
+                        {
+                            Self { value: 0 }
+                        }
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        let expected: Expr = parse_quote! { Self { value: 0 } };
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(expr) if expr == expected
+        ));
+    }
+
+    #[test]
+    fn test_trailing_return_of_const_new_becomes_tail_expression() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        let value = 1;
+                        return Self { value };
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        let expected: Expr = parse_quote! {
+            {
+                let value = 1;
+                Self { value }
+            }
+        };
+        assert!(matches!(
+            contract.constructor.unwrap().init,
+            StateInit::Inline(expr) if expr == expected
+        ));
+    }
+
+    #[test]
+    fn test_early_return_of_const_new_uses_runtime_init() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        if TESTNET {
+                            return Self { value: 1 };
+                        }
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let constructor = parse_contract(&mut input_mod, &ContractOptions::default())
+            .unwrap()
+            .constructor;
+
+        assert!(matches!(constructor.unwrap().init, StateInit::Runtime));
+    }
+
+    #[test]
+    fn test_strict_rejects_module_macros() {
+        let input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+
+                impl_getters!(Counter);
+            }
+        };
+
+        assert!(parse_contract(&mut input_mod.clone(), &ContractOptions::default()).is_ok());
+
+        let options = ContractOptions {
+            strict: true,
+            ..ContractOptions::default()
+        };
+        let error = parse_contract(&mut input_mod.clone(), &options)
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains(
+            "Items generated by a macro in the contract module aren't seen by `#[contract]`"
+        ));
+    }
+
+    #[test]
+    fn test_generic_trait_impl_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+
+                #[export]
+                impl<T: Into<u64>> Convert<T> for Counter {
+                    fn convert(&self, value: T) -> u64 {
+                        value.into()
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "The methods of a generic trait implementation can't be exported, as each exported method must be a single function of the contract. Implement the trait for concrete types instead, e.g. `impl Convert<u32> for Counter`."
+        );
+    }
+
+    #[test]
+    fn test_impl_with_generic_arguments_is_contract_impl() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod buffer {
+                pub struct Buffer<T = u32> {
+                    values: [T; 4],
+                }
+
+                impl Buffer<u32> {
+                    pub const fn new() -> Self {
+                        Self { values: [0; 4] }
+                    }
+
+                    pub fn capacity() -> usize {
+                        4
+                    }
+                }
+            }
+        };
+
+        let contract = parse_contract(&mut input_mod, &ContractOptions::default()).unwrap();
+
+        assert!(contract.constructor.is_some());
+        assert_eq!(contract.impl_blocks.len(), 1);
+        let self_ty = &contract.impl_blocks[0].self_ty;
+        assert_eq!(
+            quote!(#self_ty).to_string(),
+            quote!(Buffer<u32>).to_string()
+        );
+    }
+
+    #[test]
+    fn test_pre_hook_must_be_declared() {
+        let input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    paused: bool,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { paused: false }
+                    }
+
+                    fn check_not_paused(&self) -> Result<(), &'static str> {
+                        Ok(())
+                    }
+                }
+            }
+        };
+
+        let options = ContractOptions {
+            pre: Some(parse_quote!(check_not_paused)),
+            ..ContractOptions::default()
+        };
+        assert!(parse_contract(&mut input_mod.clone(), &options).is_ok());
+
+        let options = ContractOptions {
+            pre: Some(parse_quote!(check_paused)),
+            ..ContractOptions::default()
+        };
+        let error = parse_contract(&mut input_mod.clone(), &options)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "The contract's state has no method `check_paused` to run before the calls, as named by the `pre` option."
+        );
+    }
+
+    #[test]
+    fn test_constructor_with_receiver_is_rejected() {
+        let mut input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub fn new(&self) -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        let error = parse_contract(&mut input_mod, &ContractOptions::default())
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "The constructor `new` of the contract state must not take `self`, as it creates the state. Remove the receiver, e.g. `pub fn new() -> Self`."
+        );
+    }
+
+    #[test]
+    fn test_restricted_state_is_accepted_when_allowed() {
+        let input_mod: ItemMod = parse_quote! {
+            pub mod counter {
+                pub(crate) struct Counter {
+                    value: u64,
+                }
+
+                impl Counter {
+                    pub const fn new() -> Self {
+                        Self { value: 0 }
+                    }
+                }
+            }
+        };
+
+        assert!(parse_contract(&mut input_mod.clone(), &ContractOptions::default()).is_err());
+
+        let options = ContractOptions {
+            allow_restricted_state: true,
+            ..ContractOptions::default()
+        };
+        let contract = parse_contract(&mut input_mod.clone(), &options).unwrap();
+        assert_eq!(contract.struct_name.unwrap(), "Counter");
+        let visibility = &contract.visibility;
+        assert_eq!(
+            quote!(#visibility).to_string(),
+            quote!(pub(crate)).to_string()
+        );
+    }
 }
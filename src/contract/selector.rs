@@ -0,0 +1,202 @@
+use alloc::format;
+use syn::LitStr;
+
+/// The names of the selector schemes, as accepted by the `selector` option.
+const SCHEMES: &[&str] = &["index", "keccak"];
+
+/// The scheme deriving the selectors of the `entry` dispatch from the exported methods,
+/// set by the `selector` option.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SelectorScheme {
+    /// The index of the method in declaration order across the `impl` blocks.
+    #[default]
+    Index,
+    /// The first four bytes of the Keccak-256 hash of the export name, read as a big
+    /// endian integer, as Ethereum derives function selectors.
+    Keccak,
+}
+
+impl SelectorScheme {
+    /// Parses the scheme named by the `selector` option, e.g. `selector = "keccak"`.
+    ///
+    /// # Errors
+    /// If the name isn't one of the supported schemes.
+    pub fn parse(name: &LitStr) -> syn::Result<Self> {
+        match name.value().as_str() {
+            "index" => Ok(Self::Index),
+            "keccak" => Ok(Self::Keccak),
+            scheme => Err(syn::Error::new_spanned(
+                name,
+                format!(
+                    "unsupported selector `{scheme}`, expected one of: {}",
+                    SCHEMES.join(", ")
+                ),
+            )),
+        }
+    }
+
+    /// Returns the selector of the method exported under the given name, at the given
+    /// index in declaration order.
+    pub fn selector(self, index: usize, name: &str) -> u32 {
+        match self {
+            Self::Index => index as u32,
+            Self::Keccak => {
+                let hash = keccak256(name.as_bytes());
+                u32::from_be_bytes([hash[0], hash[1], hash[2], hash[3]])
+            }
+        }
+    }
+}
+
+/// The round constants of Keccak-f[1600].
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000_0000_0000_0001,
+    0x0000_0000_0000_8082,
+    0x8000_0000_0000_808a,
+    0x8000_0000_8000_8000,
+    0x0000_0000_0000_808b,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8009,
+    0x0000_0000_0000_008a,
+    0x0000_0000_0000_0088,
+    0x0000_0000_8000_8009,
+    0x0000_0000_8000_000a,
+    0x0000_0000_8000_808b,
+    0x8000_0000_0000_008b,
+    0x8000_0000_0000_8089,
+    0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002,
+    0x8000_0000_0000_0080,
+    0x0000_0000_0000_800a,
+    0x8000_0000_8000_000a,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8080,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8008,
+];
+
+/// The rotation offsets of the lanes visited by the rho and pi steps.
+const ROTATIONS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// The lanes visited by the rho and pi steps, in order.
+const PI_LANES: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// The number of bytes absorbed per block by Keccak-256.
+const RATE: usize = 136;
+
+/// Hashes the given data with Keccak-256, the variant of SHA-3 padded as in the original
+/// Keccak submission, which Ethereum uses.
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+
+    // Absorb the data padded to a multiple of the rate, with `0x01 .. 0x80`
+    let blocks = data.len() / RATE + 1;
+    for block in 0..blocks {
+        let mut bytes = [0u8; RATE];
+        let start = block * RATE;
+        let chunk = &data[start..data.len().min(start + RATE)];
+        bytes[..chunk.len()].copy_from_slice(chunk);
+        if block == blocks - 1 {
+            bytes[chunk.len()] ^= 0x01;
+            bytes[RATE - 1] ^= 0x80;
+        }
+        for (lane, word) in state.iter_mut().zip(bytes.chunks_exact(8)) {
+            // A chunk of `chunks_exact(8)` always has 8 bytes
+            *lane ^= u64::from_le_bytes(word.try_into().unwrap());
+        }
+        keccak_f(&mut state);
+    }
+
+    let mut hash = [0u8; 32];
+    for (bytes, lane) in hash.chunks_exact_mut(8).zip(state) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+    hash
+}
+
+/// Applies the Keccak-f[1600] permutation to the state.
+fn keccak_f(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // Theta
+        let mut columns = [0u64; 5];
+        for (x, column) in columns.iter_mut().enumerate() {
+            *column = state[x] ^ state[x + 5] ^ state[x + 10] ^ state[x + 15] ^ state[x + 20];
+        }
+        for x in 0..5 {
+            let parity = columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+            for y in (0..25).step_by(5) {
+                state[y + x] ^= parity;
+            }
+        }
+
+        // Rho and pi
+        let mut lane = state[1];
+        for (&target, &rotation) in PI_LANES.iter().zip(&ROTATIONS) {
+            let next = state[target];
+            state[target] = lane.rotate_left(rotation);
+            lane = next;
+        }
+
+        // Chi
+        for y in (0..25).step_by(5) {
+            let row = [
+                state[y],
+                state[y + 1],
+                state[y + 2],
+                state[y + 3],
+                state[y + 4],
+            ];
+            for x in 0..5 {
+                state[y + x] = row[x] ^ (!row[(x + 1) % 5] & row[(x + 2) % 5]);
+            }
+        }
+
+        // Iota
+        state[0] ^= round_constant;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keccak256, SelectorScheme};
+    use alloc::string::ToString;
+    use syn::LitStr;
+
+    #[test]
+    fn test_keccak256_matches_known_hashes() {
+        let empty = [
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ];
+        assert_eq!(keccak256(b""), empty);
+
+        // Data longer than a block is absorbed in several blocks
+        let long = [b'a'; 200];
+        assert_ne!(keccak256(&long), keccak256(&long[..136]));
+    }
+
+    #[test]
+    fn test_keccak_selector_matches_ethereum() {
+        let selector = SelectorScheme::Keccak.selector(0, "transfer(address,uint256)");
+
+        assert_eq!(selector, 0xa905_9cbb);
+    }
+
+    #[test]
+    fn test_unknown_scheme_is_rejected() {
+        let name: LitStr = syn::parse_quote!("sha256");
+
+        let error = SelectorScheme::parse(&name).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "unsupported selector `sha256`, expected one of: index, keccak"
+        );
+    }
+}
@@ -1,6 +1,15 @@
+use crate::contract::options::ContractOptions;
+use crate::contract::parser::{Constructor, StateInit};
 use crate::contract::transformation::ReplaceSelfWithStructName;
-use proc_macro::TokenStream;
-use syn::{visit_mut::VisitMut, Expr, Ident, ItemMod};
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::{
+    punctuated::Punctuated, visit_mut::VisitMut, ConstParam, GenericParam, Generics, Ident, Item,
+    ItemMod, ItemStatic, Path, Token, Type, TypeParam, Visibility,
+};
 
 /// This function creates a `static mut STATE` variable, initialized using the `new` function
 /// of the struct, and appends it to the module. It ensures that `Self` in the `new` function
@@ -8,19 +17,47 @@ use syn::{visit_mut::VisitMut, Expr, Ident, ItemMod};
 ///
 /// The `static mut STATE` variable serves as the state of a contract.
 ///
+/// When the constructor can't be evaluated at compile time (see [`StateInit`]), the state
+/// is declared as `static mut STATE: Option<MyStruct> = None` instead and assigned at
+/// runtime by the generated `init` export. With the `boxed_state` option, the state is
+/// declared as `static mut STATE: Option<Box<MyStruct>> = None`.
+///
+/// A `static mut STATE` declared by the user in the module, e.g. to give it custom
+/// attributes, is used as the state instead, and no declaration is appended. It must have
+/// the type of the generated declaration.
+///
+/// With the `max_state_size` option, the size of the state is asserted at compile time,
+/// see [`state_size_assertion`].
+///
+/// With the `state_section` option, the state is placed in the given link section with
+/// `#[link_section = "..."]`.
+///
+/// The state is declared `pub(crate)`, or with the visibility of the struct if it's more
+/// restricted, e.g. `pub(super)` with the `allow_restricted_state` option.
+///
+/// A struct with generic parameters is named with their defaults in the type of `STATE`,
+/// e.g. `Buffer<32>` for `pub struct Buffer<const N: usize = 32>`, which is also the type
+/// an `impl Buffer` block is written for.
+///
 /// # Parameters
 /// - `struct_name`: The name of the public struct.
-/// - `new_function_body`: The body of the `new` function, which initializes the struct.
+/// - `generics`: The generic parameters of the public struct.
+/// - `visibility`: The visibility of the public struct.
+/// - `constructor`: The `new` function, which initializes the struct.
+/// - `options`: The options passed to the `#[contract]` attribute, which determine
+///   whether the state is allocated on the heap and its link section.
 /// - `input_mod`: The mutable reference to the module where the `STATE` declaration is appended.
 ///
 /// # Returns
 /// - `Ok(())` if the `STATE` declaration is successfully added to the module.
-/// - `Err(TokenStream)` if the `new` function is missing or invalid.
+/// - `Err(syn::Error)` if the `new` function is missing or invalid.
 ///
 /// # Errors
 /// This function returns an error if:
 /// - The `new` function is not found.
 /// - The `new` function's body is missing or invalid.
+/// - A generic type or const parameter of the struct has no default.
+/// - The module declares a `static mut STATE` of another type than the generated one.
 ///
 /// # Example
 ///
@@ -71,32 +108,271 @@ use syn::{visit_mut::VisitMut, Expr, Ident, ItemMod};
 /// ```
 pub fn generate_state_declaration(
     struct_name: &Ident,
-    new_function_body: Option<Expr>,
+    generics: &Generics,
+    visibility: &Visibility,
+    constructor: Option<&Constructor>,
+    options: &ContractOptions,
     input_mod: &mut ItemMod,
-) -> Result<(), TokenStream> {
+) -> syn::Result<()> {
+    let state_type = state_type(struct_name, generics)?;
+    let vis = match visibility {
+        Visibility::Restricted(_) => quote! { #visibility },
+        Visibility::Public(_) | Visibility::Inherited => quote! { pub(crate) },
+    };
+
     // Ensure the `new` function was found
-    let mut transformed_body = match new_function_body {
-        Some(body) => body,
+    let constructor = match constructor {
+        Some(constructor) => constructor,
         None => {
             return Err(syn::Error::new_spanned(
                 input_mod,
                 "The struct must implement a `new` function for initializing the contract state.",
-            )
-            .to_compile_error()
-            .into());
+            ));
         }
     };
 
-    // Replace `Self` with the struct name in the `new` function body
-    ReplaceSelfWithStructName { struct_name }.visit_expr_mut(&mut transformed_body);
+    let boxed = options.boxed_state;
+    let link_section = options
+        .state_section
+        .as_ref()
+        .map(|section| quote! { #[link_section = #section] });
+    let declaration = match &constructor.init {
+        StateInit::Inline(body) => {
+            // Replace `Self` with the struct name in the `new` function body
+            let mut transformed_body = body.clone();
+            ReplaceSelfWithStructName { struct_name }.visit_expr_mut(&mut transformed_body);
 
-    // Append the static state declaration to the module
+            syn::parse_quote! {
+                #link_section
+                #vis static mut STATE: #state_type = #transformed_body;
+            }
+        }
+        StateInit::Runtime if boxed => syn::parse_quote! {
+            #link_section
+            #vis static mut STATE: Option<alloc::boxed::Box<#state_type>> = None;
+        },
+        StateInit::Runtime => syn::parse_quote! {
+            #link_section
+            #vis static mut STATE: Option<#state_type> = None;
+        },
+    };
+
+    // Append the static state declaration to the module, unless declared by the user
     if let Some((_, items)) = &mut input_mod.content {
-        // Holds the contract's state. This is automatically generated
-        items.push(syn::parse_quote! {
-            pub(crate) static mut STATE: #struct_name = #transformed_body;
-        });
+        match user_state(items) {
+            Some(user_state) => validate_user_state(user_state, &declaration)?,
+            // Holds the contract's state. This is automatically generated
+            None => items.push(declaration),
+        }
+        if let Some(max_size) = options.max_state_size {
+            items.push(state_size_assertion(&state_type, max_size));
+        }
     }
 
     Ok(())
 }
+
+/// Returns the `static mut STATE` declared by the user in the module, if any.
+fn user_state(items: &[Item]) -> Option<&ItemStatic> {
+    items.iter().find_map(|item| match item {
+        Item::Static(item) if item.ident == "STATE" => Some(item),
+        _ => None,
+    })
+}
+
+/// Validates that the `static mut STATE` declared by the user has the type of the state
+/// the macro would generate, through which the wrappers access it.
+///
+/// # Errors
+/// If the types differ, e.g. `Counter` for a state initialized at runtime, which is
+/// declared as `Option<Counter>`.
+fn validate_user_state(user_state: &ItemStatic, declaration: &Item) -> syn::Result<()> {
+    let Item::Static(declaration) = declaration else {
+        unreachable!("the state is declared as a static");
+    };
+    let expected = declaration.ty.to_token_stream().to_string();
+    if user_state.ty.to_token_stream().to_string() != expected {
+        let expected = expected.replace(' ', "");
+        return Err(syn::Error::new_spanned(
+            &user_state.ty,
+            format!("The `STATE` declared by the module must have the type `{expected}`, through which the generated wrappers access the contract state."),
+        ));
+    }
+    Ok(())
+}
+
+/// Generates the assertion that the state fits in the size set by the `max_state_size`
+/// option, so that a state growing beyond it fails at compile time.
+///
+/// # Example
+/// A state of 32 bytes doesn't fit in 16:
+/// ```compile_fail,E0080
+/// use dusk_forge::contract;
+///
+/// #[contract(max_state_size = 16)]
+/// pub mod my_contract {
+///     pub struct MyStruct {
+///         values: [u64; 4],
+///     }
+///
+///     impl MyStruct {
+///         pub const fn new() -> Self {
+///             Self { values: [0; 4] }
+///         }
+///     }
+/// }
+/// ```
+fn state_size_assertion(state_type: &TokenStream, max_size: usize) -> Item {
+    syn::parse_quote! {
+        const _: () = assert!(
+            core::mem::size_of::<#state_type>() <= #max_size,
+            "the contract state exceeds the size set by `max_state_size`"
+        );
+    }
+}
+
+/// Generates the `snapshot` export of the `snapshot` option, returning a clone of the
+/// contract state, e.g. for debugging or indexing.
+///
+/// As the macro can't resolve trait implementations, the struct is required to derive
+/// `Clone`, or to implement it in the module.
+///
+/// # Parameters
+/// - `struct_name`: The name of the public struct.
+/// - `state`: The expression accessing the contract state, see [`state_access`].
+/// - `input_mod`: The module declaring the struct.
+///
+/// # Errors
+/// If the struct neither derives nor implements `Clone` in the module.
+pub fn generate_snapshot_function(
+    struct_name: &Ident,
+    state: &TokenStream,
+    input_mod: &ItemMod,
+) -> syn::Result<TokenStream> {
+    let items = input_mod
+        .content
+        .as_ref()
+        .map_or(&[][..], |(_, items)| items.as_slice());
+    if !implements_clone(struct_name, items) {
+        return Err(syn::Error::new_spanned(
+            struct_name,
+            format!("The `snapshot` option requires the contract state to implement `Clone`. Add `#[derive(Clone)]` to `{struct_name}`."),
+        ));
+    }
+
+    Ok(quote! {
+        // A `no_mangle` function returning a clone of the contract state.
+        #[no_mangle]
+        pub unsafe fn snapshot(arg_len: u32) -> u32 {
+            dusk_core::abi::wrap_call(arg_len, |(): ()| #state.clone())
+        }
+    })
+}
+
+/// Returns whether the struct derives `Clone`, or implements it in the given items.
+fn implements_clone(struct_name: &Ident, items: &[Item]) -> bool {
+    let is_clone = |path: &Path| path.segments.last().is_some_and(|s| s.ident == "Clone");
+    items.iter().any(|item| match item {
+        Item::Struct(item) if item.ident == *struct_name => item
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("derive"))
+            .any(|attr| {
+                attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                    .is_ok_and(|derives| derives.iter().any(is_clone))
+            }),
+        Item::Impl(item) => match (&item.trait_, &*item.self_ty) {
+            (Some((_, path, _)), Type::Path(ty)) => {
+                is_clone(path)
+                    && ty
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|s| s.ident == *struct_name)
+            }
+            _ => false,
+        },
+        _ => false,
+    })
+}
+
+/// Names the type of the contract state, substituting the defaults of the generic
+/// parameters of the struct, e.g. `Buffer<32>` for `pub struct Buffer<const N: usize = 32>`.
+///
+/// Lifetime parameters are substituted with `'static`, as the state is a `static`.
+///
+/// # Errors
+/// If a type or const parameter has no default, as the state can't be named without it.
+fn state_type(struct_name: &Ident, generics: &Generics) -> syn::Result<TokenStream> {
+    if generics.params.is_empty() {
+        return Ok(quote! { #struct_name });
+    }
+
+    let arguments = generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Lifetime(_) => Ok(quote! { 'static }),
+            GenericParam::Type(TypeParam {
+                default: Some(default),
+                ..
+            }) => Ok(quote! { #default }),
+            GenericParam::Const(ConstParam {
+                default: Some(default),
+                ..
+            }) => Ok(quote! { { #default } }),
+            GenericParam::Type(TypeParam { ident, .. })
+            | GenericParam::Const(ConstParam { ident, .. }) => Err(syn::Error::new_spanned(
+                param,
+                format!("The generic parameter `{ident}` of the contract's state `{struct_name}` must have a default, e.g. `const N: usize = 32`, for the type of `STATE` to be named."),
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! { #struct_name<#(#arguments),*> })
+}
+
+/// Generates the expression through which the `no_mangle` wrappers, emitted in the
+/// contract module, access the contract state.
+///
+/// An inlined state is accessed directly. A state initialized at runtime is taken out of
+/// its `Option`: a constructor without arguments is run lazily on first access, since the
+/// host only calls `init` when constructor arguments are provided on deployment. A boxed
+/// state is additionally dereferenced through its `Box`.
+///
+/// # Parameters
+/// - `struct_name`: The name of the public struct.
+/// - `constructor`: The constructor, either marked `#[init]` or named `new`.
+/// - `options`: The options passed to the `#[contract]` attribute.
+pub fn state_access(
+    struct_name: &Ident,
+    constructor: &Constructor,
+    options: &ContractOptions,
+) -> TokenStream {
+    let boxed = options.boxed_state;
+    let uninitialized = format!(
+        "the contract state must be initialized by calling `{}`",
+        options.init_name()
+    );
+    let ctor = &constructor.function.sig.ident;
+    match constructor.init {
+        StateInit::Inline(_) => quote! { STATE },
+        StateInit::Runtime if boxed && constructor.function.sig.inputs.is_empty() => quote! {
+            (**STATE
+                .get_or_insert_with(|| alloc::boxed::Box::new(#struct_name::#ctor())))
+        },
+        StateInit::Runtime if boxed => quote! {
+            (**STATE
+                .as_mut()
+                .expect(#uninitialized))
+        },
+        StateInit::Runtime if constructor.function.sig.inputs.is_empty() => quote! {
+            STATE.get_or_insert_with(#struct_name::#ctor)
+        },
+        StateInit::Runtime => quote! {
+            STATE
+                .as_mut()
+                .expect(#uninitialized)
+        },
+    }
+}
@@ -1,5 +1,4 @@
-use alloc::vec;
-use syn::{punctuated::Punctuated, visit_mut, visit_mut::VisitMut, Ident, Path, PathSegment};
+use syn::{visit_mut, visit_mut::VisitMut, Ident, Path};
 
 /// A transformation that replaces occurrences of `Self` with a specified struct name.
 ///
@@ -12,15 +11,20 @@ pub struct ReplaceSelfWithStructName<'a> {
 impl<'a> VisitMut for ReplaceSelfWithStructName<'a> {
     /// Visits mutable paths in the syntax tree and replaces `Self` with the struct name.
     ///
-    /// This method is called recursively on all paths within the syntax tree.
+    /// Both a plain `Self` and a path starting with `Self`, such as `Self::Key`, are
+    /// rewritten. This method is called recursively on all paths within the syntax tree,
+    /// including the generic arguments of a path, e.g. `Vec<Self>`.
     ///
     /// # Parameters
     /// - `path`: A mutable reference to a `Path` in the syntax tree.
     fn visit_path_mut(&mut self, path: &mut Path) {
-        if path.is_ident("Self") {
-            // Replace `Self` with the struct name
-            path.segments =
-                Punctuated::from_iter(vec![PathSegment::from(self.struct_name.clone())]);
+        if path.leading_colon.is_none() {
+            if let Some(first) = path.segments.first_mut() {
+                if first.ident == "Self" {
+                    // Replace `Self` with the struct name
+                    first.ident = self.struct_name.clone();
+                }
+            }
         }
         // Continue visiting nested paths
         visit_mut::visit_path_mut(self, path);
@@ -32,7 +36,7 @@ mod tests {
     use super::ReplaceSelfWithStructName;
     use alloc::string::ToString;
     use syn::__private::ToTokens;
-    use syn::{parse_quote, visit_mut::VisitMut, Ident, Path};
+    use syn::{parse_quote, visit_mut::VisitMut, Expr, Ident, Path, Type};
 
     #[test]
     fn test_replace_self_with_struct_name() {
@@ -47,4 +51,71 @@ mod tests {
 
         assert_eq!(path.to_token_stream().to_string(), "MyStruct");
     }
+
+    #[test]
+    fn test_replace_self_in_associated_type_path() {
+        let mut path: Path = parse_quote! { Self::Key };
+
+        let struct_name: Ident = syn::parse_str("MyStruct").unwrap();
+
+        ReplaceSelfWithStructName {
+            struct_name: &struct_name,
+        }
+        .visit_path_mut(&mut path);
+
+        assert_eq!(path.to_token_stream().to_string(), "MyStruct :: Key");
+    }
+
+    #[test]
+    fn test_replace_self_in_qualified_path() {
+        let mut ty: Type = parse_quote! { <Self as Token>::Balance };
+
+        let struct_name: Ident = syn::parse_str("MyStruct").unwrap();
+
+        ReplaceSelfWithStructName {
+            struct_name: &struct_name,
+        }
+        .visit_type_mut(&mut ty);
+
+        assert_eq!(
+            ty.to_token_stream().to_string(),
+            "< MyStruct as Token > :: Balance"
+        );
+    }
+
+    #[test]
+    fn test_replace_self_in_generic_arguments() {
+        let mut ty: Type = parse_quote! { Option<Vec<Self>> };
+
+        let struct_name: Ident = syn::parse_str("MyStruct").unwrap();
+
+        ReplaceSelfWithStructName {
+            struct_name: &struct_name,
+        }
+        .visit_type_mut(&mut ty);
+
+        assert_eq!(
+            ty.to_token_stream().to_string(),
+            "Option < Vec < MyStruct > >"
+        );
+    }
+
+    #[test]
+    fn test_phantom_data_is_kept() {
+        let mut expr: Expr = parse_quote! {
+            Self { supply: 0, kind: core::marker::PhantomData, tag: PhantomData::<Self> }
+        };
+
+        let struct_name: Ident = syn::parse_str("MyStruct").unwrap();
+
+        ReplaceSelfWithStructName {
+            struct_name: &struct_name,
+        }
+        .visit_expr_mut(&mut expr);
+
+        assert_eq!(
+            expr.to_token_stream().to_string(),
+            "MyStruct { supply : 0 , kind : core :: marker :: PhantomData , tag : PhantomData :: < MyStruct > }"
+        );
+    }
 }
@@ -3,7 +3,6 @@
 //! automatically generate the boilerplate code required for interfacing smart
 //! contracts with the Dusk VM.
 
-#![feature(proc_macro_quote)]
 #![no_std]
 extern crate alloc;
 extern crate proc_macro;
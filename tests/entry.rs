@@ -0,0 +1,125 @@
+//! Calls the `entry` export generated by `#[contract]` with argument buffers encoded with
+//! rkyv, checking that it reads the selector and the arguments of the method from them.
+
+use rkyv::AlignedVec;
+use std::sync::{Mutex, MutexGuard};
+
+/// A stand-in for `dusk_core::abi`, whose `wrap_call` decodes the argument buffer with
+/// rkyv as the one of `piecrust_uplink` does.
+mod dusk_core {
+    pub mod abi {
+        use rkyv::validation::validators::DefaultValidator;
+        use rkyv::{check_archived_root, Archive, Deserialize, Infallible};
+        use std::any::Any;
+        use std::sync::Mutex;
+
+        /// The argument buffer of the contract, written by the test.
+        pub static ARGUMENT_BUFFER: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+        /// The result of the last call.
+        pub static RESULT: Mutex<Option<Box<dyn Any + Send>>> = Mutex::new(None);
+
+        pub fn wrap_call<A, R, F>(arg_len: u32, f: F) -> u32
+        where
+            A: Archive,
+            A::Archived:
+                Deserialize<A, Infallible> + for<'b> bytecheck::CheckBytes<DefaultValidator<'b>>,
+            R: Any + Send,
+            F: Fn(A) -> R,
+        {
+            let buffer = ARGUMENT_BUFFER.lock().unwrap().clone();
+            let mut aligned = rkyv::AlignedVec::new();
+            aligned.extend_from_slice(&buffer);
+            let archived = check_archived_root::<A>(&aligned[..arg_len as usize])
+                .expect("Argument should correctly deserialize");
+            let arguments: A = archived.deserialize(&mut Infallible).unwrap();
+            let result = f(arguments);
+            *RESULT.lock().unwrap() = Some(Box::new(result));
+            arg_len
+        }
+    }
+}
+
+#[dusk_forge::contract(entry = "call")]
+pub mod counter {
+    use crate::dusk_core;
+
+    pub struct Counter {
+        value: i64,
+    }
+
+    impl Counter {
+        pub const fn new() -> Self {
+            Self { value: 0 }
+        }
+
+        pub fn read_value(&self) -> i64 {
+            self.value
+        }
+
+        pub fn add(&mut self, a: i64, b: i64) {
+            self.value += a + b;
+        }
+
+        pub fn sum(values: Vec<u64>) -> u64 {
+            values.iter().sum()
+        }
+    }
+}
+
+/// Serializes the tests, as they share the state of the contract.
+static CONTRACT: Mutex<()> = Mutex::new(());
+
+fn lock_contract() -> MutexGuard<'static, ()> {
+    CONTRACT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Calls the entry with a buffer holding the selector as a little-endian `u32` padded to 8
+/// bytes, followed by the arguments archived with rkyv, returning the result.
+fn call<A, R>(selector: u32, arguments: &A) -> R
+where
+    A: rkyv::Serialize<rkyv::ser::serializers::AllocSerializer<256>>,
+    R: 'static,
+{
+    let archived: AlignedVec = rkyv::to_bytes::<_, 256>(arguments).unwrap();
+    let mut buffer = selector.to_le_bytes().to_vec();
+    buffer.resize(8, 0);
+    buffer.extend_from_slice(&archived);
+    let len = buffer.len() as u32;
+    *dusk_core::abi::ARGUMENT_BUFFER.lock().unwrap() = buffer;
+
+    assert_eq!(unsafe { counter::call(len) }, len);
+    let result = dusk_core::abi::RESULT.lock().unwrap().take().unwrap();
+    *result
+        .downcast::<R>()
+        .expect("the result must be returned as the method's return type")
+}
+
+#[test]
+fn test_entry_decodes_selector_and_arguments() {
+    let _contract = lock_contract();
+    let value = call::<_, i64>(0, &());
+
+    call::<_, ()>(1, &(3i64, 4i64));
+
+    assert_eq!(call::<_, i64>(0, &()), value + 7);
+}
+
+#[test]
+fn test_entry_decodes_out_of_line_arguments() {
+    let _contract = lock_contract();
+
+    // The archived `Vec` stores its elements before its root, after the selector
+    assert_eq!(call::<_, u64>(2, &vec![1u64, 2, 3]), 6);
+}
+
+#[test]
+#[should_panic(expected = "unknown method selector")]
+fn test_entry_rejects_unknown_selector() {
+    let _contract = CONTRACT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    call::<_, ()>(3, &());
+}
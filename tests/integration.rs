@@ -0,0 +1,565 @@
+//! Invokes the wrappers generated by `#[contract]`, checking that each of them decodes its
+//! arguments and dispatches to the right method of the contract.
+
+use std::any::Any;
+use std::sync::{Mutex, MutexGuard};
+
+/// A stand-in for `dusk_core::abi`, decoding the arguments queued by the test instead of
+/// the argument buffer of the Dusk VM, and recording the calls and their results.
+mod dusk_core {
+    pub mod abi {
+        use std::any::Any;
+        use std::sync::Mutex;
+
+        /// The arguments of the next call, queued by the test.
+        pub static ARGUMENTS: Mutex<Option<Box<dyn Any + Send>>> = Mutex::new(None);
+        /// The result of the last call.
+        pub static RESULT: Mutex<Option<Box<dyn Any + Send>>> = Mutex::new(None);
+        /// The number of calls made through `wrap_call`.
+        pub static CALLS: Mutex<usize> = Mutex::new(0);
+
+        /// The value deposited by the transaction, checked by the claims of the contracts.
+        pub static DEPOSIT: Mutex<u64> = Mutex::new(0);
+
+        /// The caller reported to the contract, the transfer contract as for a call made by
+        /// a transaction.
+        pub fn caller() -> Option<u64> {
+            Some(super::transfer::TRANSFER_CONTRACT)
+        }
+
+        /// Calls the transfer contract, whose `deposit` claims the deposit of the transaction
+        /// and fails if nothing is deposited or the claimed value doesn't match, and whose
+        /// `transaction_deposit` returns the deposit of the transaction.
+        pub fn call<A: Any, R: Any>(contract: u64, name: &str, args: &A) -> Result<R, String> {
+            assert_eq!(contract, super::transfer::TRANSFER_CONTRACT);
+            let mut deposit = DEPOSIT.lock().unwrap();
+            let result: Box<dyn Any> = match name {
+                "deposit" => {
+                    let value = (args as &dyn Any)
+                        .downcast_ref::<u64>()
+                        .expect("the deposit must be claimed as a `u64`");
+                    if *deposit == 0 {
+                        return Err(String::from("there is no deposit to claim"));
+                    }
+                    if *value != *deposit {
+                        return Err(String::from("the claimed value doesn't match the deposit"));
+                    }
+                    *deposit = 0;
+                    Box::new(())
+                }
+                "transaction_deposit" => Box::new(*deposit),
+                _ => panic!("the transfer contract has no `{name}` method"),
+            };
+            Ok(*result
+                .downcast::<R>()
+                .expect("the result must be returned as `R`"))
+        }
+
+        pub fn wrap_call<A: Any, R: Any + Send, F: Fn(A) -> R>(arg_len: u32, f: F) -> u32 {
+            let arguments = ARGUMENTS
+                .lock()
+                .unwrap()
+                .take()
+                .expect("no arguments queued");
+            // Panics as `piecrust_uplink` does for a buffer that isn't an archive of `A`
+            let arguments = *arguments
+                .downcast::<A>()
+                .expect("Argument should correctly deserialize");
+            let result = f(arguments);
+            *RESULT.lock().unwrap() = Some(Box::new(result));
+            *CALLS.lock().unwrap() += 1;
+            arg_len
+        }
+    }
+
+    pub mod transfer {
+        /// The ID of the transfer contract.
+        pub const TRANSFER_CONTRACT: u64 = 7;
+    }
+}
+
+/// Types of the contracts' arguments, declared outside of the contract modules and shared
+/// by them.
+mod types {
+    pub struct Amount(pub i64);
+
+    /// Names the balance type of a contract.
+    pub trait Ledger {
+        type Balance;
+    }
+
+    /// Tags the state of a contract at the type level.
+    pub struct Dusk;
+
+    /// The state of the `vault` contract, re-exported by its module.
+    pub struct Vault {
+        pub balance: u64,
+    }
+}
+
+mod contracts {
+    #[dusk_forge::contract(client, snapshot, test_exports)]
+    pub mod counter {
+        use crate::dusk_core;
+        // Brought in scope of the wrappers, which are emitted in the module
+        use crate::types::{Amount, Ledger};
+
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct Counter {
+            value: i64,
+        }
+
+        impl Ledger for Counter {
+            type Balance = i64;
+        }
+
+        impl Counter {
+            pub fn new(value: i64) -> Self {
+                Self { value }
+            }
+
+            pub fn add(&mut self, a: i64, b: i64) {
+                self.value += a + b;
+            }
+
+            pub fn credit(&mut self, amount: <Self as Ledger>::Balance) {
+                self.value += amount;
+            }
+
+            pub fn add_times(&mut self, amount: i64, #[default(1)] times: i64) {
+                self.value += amount * times;
+            }
+
+            pub fn deposit(&mut self, amount: Amount) {
+                self.value += amount.0;
+            }
+
+            #[payable]
+            pub fn stake(&mut self, value: u64) {
+                self.value += value as i64;
+            }
+
+            #[validate(check_amount)]
+            pub fn withdraw(&mut self, amount: i64) {
+                self.value -= amount;
+            }
+
+            fn check_amount(amount: &i64) -> Result<(), &'static str> {
+                match *amount > 0 {
+                    true => Ok(()),
+                    false => Err("the amount must be positive"),
+                }
+            }
+
+            pub fn add_caller(&mut self, #[caller] caller: Option<u64>, times: i64) {
+                self.value += caller.unwrap() as i64 * times;
+            }
+
+            #[export(alias = "get_value")]
+            pub fn read_value(&self) -> i64 {
+                self.value
+            }
+
+            pub fn value_above(&self, threshold: i64) -> Option<i64> {
+                (self.value > threshold).then_some(self.value)
+            }
+
+            pub fn checksum(key: [u8; 32], signature: [u8; 64]) -> u64 {
+                key.iter().chain(&signature).map(|byte| *byte as u64).sum()
+            }
+
+            pub fn double(value: i64) -> i64 {
+                value * 2
+            }
+
+            pub fn revert(&self, code: u32) -> ! {
+                panic!("reverted with code {code} at {}", self.value)
+            }
+
+            #[uses_state]
+            pub fn scaled(state: &Self, factor: i64) -> i64 {
+                state.value * factor
+            }
+        }
+    }
+
+    // The state is two `u64`, `PhantomData` taking no space
+    #[dusk_forge::contract(max_state_size = 16, keep_exports)]
+    pub mod tagged {
+        use crate::dusk_core;
+        // Shared with the `counter` contract
+        use crate::types::{Amount, Dusk};
+        use core::marker::PhantomData;
+
+        /// Referenced by the state initializer, which is emitted in the module.
+        const MAX_CAP: u64 = 1000;
+
+        /// Named by the signatures of the methods, and resolved in the module.
+        pub type Supply = u64;
+
+        pub struct Tagged {
+            supply: u64,
+            cap: u64,
+            currency: PhantomData<Dusk>,
+        }
+
+        impl Tagged {
+            pub const fn new() -> Self {
+                Self {
+                    supply: 21,
+                    cap: MAX_CAP,
+                    currency: PhantomData,
+                }
+            }
+
+            pub fn tagged_supply(&self) -> u64 {
+                self.supply
+            }
+
+            pub fn tagged_cap(&self) -> u64 {
+                self.cap
+            }
+
+            pub fn tagged_above(&self, supply: Supply) -> Supply {
+                self.supply.saturating_sub(supply)
+            }
+
+            pub fn tagged_headroom(&self, amount: Amount) -> i64 {
+                self.cap as i64 - self.supply as i64 - amount.0
+            }
+        }
+    }
+
+    #[dusk_forge::contract(deposit_query = "transaction_deposit")]
+    pub mod vault {
+        use crate::dusk_core;
+
+        #[state]
+        pub use crate::types::Vault;
+
+        impl Vault {
+            pub const fn new() -> Self {
+                Self { balance: 30 }
+            }
+
+            pub fn vault_balance(&self) -> u64 {
+                self.balance
+            }
+        }
+    }
+
+    // Pure functions, without a state
+    #[dusk_forge::contract(no_state)]
+    pub mod hasher {
+        use crate::dusk_core;
+
+        pub struct Hasher;
+
+        impl Hasher {
+            pub fn digest(data: Vec<u8>) -> u64 {
+                data.iter().map(|byte| *byte as u64).sum()
+            }
+        }
+    }
+}
+
+use contracts::{counter, hasher, tagged, vault};
+
+/// Serializes the tests, as they share the state of the contract.
+static CONTRACT: Mutex<()> = Mutex::new(());
+
+fn lock_contract() -> MutexGuard<'static, ()> {
+    CONTRACT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Calls a wrapper with the given arguments, returning the result of the method.
+fn call<A: Any + Send, R: Any>(wrapper: unsafe fn(u32) -> u32, arguments: A) -> R {
+    *dusk_core::abi::ARGUMENTS.lock().unwrap() = Some(Box::new(arguments));
+    assert_eq!(unsafe { wrapper(4) }, 4);
+    let result = dusk_core::abi::RESULT.lock().unwrap().take().unwrap();
+    *result
+        .downcast::<R>()
+        .expect("the result must be returned as the method's return type")
+}
+
+#[test]
+fn test_init_calls_constructor() {
+    let _contract = lock_contract();
+
+    call::<_, ()>(counter::init, 5i64);
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 5);
+}
+
+#[test]
+fn test_deploy_args_are_decoded_by_init() {
+    let _contract = lock_contract();
+
+    let args = counter::DeployArgs { value: 8 };
+    call::<_, ()>(counter::init, args.into_init_arg());
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 8);
+}
+
+#[test]
+fn test_test_exports_call_methods_natively() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 2i64);
+    let calls = *dusk_core::abi::CALLS.lock().unwrap();
+
+    // Serialized with the other tests by the lock of the contract
+    unsafe {
+        counter::__call_add(3, 4);
+        counter::__call_add_times(1, None);
+
+        assert_eq!(counter::__call_read_value(), 10);
+        assert_eq!(counter::__call_double(4), 8);
+    }
+    assert_eq!(*dusk_core::abi::CALLS.lock().unwrap(), calls);
+}
+
+#[test]
+fn test_snapshot_returns_clone_of_state() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 4i64);
+
+    let snapshot = call::<_, counter::Counter>(counter::snapshot, ());
+
+    assert_eq!(snapshot, counter::Counter::new(4));
+}
+
+#[test]
+fn test_instance_method_decodes_arguments() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 1i64);
+
+    call::<_, ()>(counter::add, (2i64, 3i64));
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 6);
+}
+
+#[test]
+fn test_qualified_associated_argument_type_is_decoded() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 1i64);
+
+    call::<_, ()>(counter::credit, 4i64);
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 5);
+}
+
+#[test]
+fn test_default_argument_is_substituted() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 1i64);
+
+    call::<_, ()>(counter::add_times, (2i64, None::<i64>));
+    call::<_, ()>(counter::add_times, (2i64, Some(3i64)));
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 9);
+}
+
+#[test]
+fn test_argument_type_imported_in_module_is_decoded() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 1i64);
+
+    call::<_, ()>(counter::deposit, types::Amount(9));
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 10);
+}
+
+#[test]
+fn test_validated_method_accepts_valid_arguments() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 10i64);
+
+    call::<_, ()>(counter::withdraw, 4i64);
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 6);
+}
+
+#[test]
+#[should_panic(expected = "invalid arguments for `withdraw`")]
+fn test_validated_method_rejects_invalid_arguments() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 10i64);
+
+    call::<_, ()>(counter::withdraw, -4i64);
+}
+
+#[test]
+fn test_caller_argument_is_passed_by_the_vm() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 0i64);
+
+    call::<_, ()>(counter::add_caller, 2i64);
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 14);
+}
+
+#[test]
+fn test_alias_calls_the_same_method() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 3i64);
+
+    assert_eq!(call::<_, i64>(counter::get_value, ()), 3);
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 3);
+}
+
+#[test]
+fn test_option_is_returned() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    assert_eq!(call::<_, Option<i64>>(counter::value_above, 4i64), Some(5));
+    assert_eq!(call::<_, Option<i64>>(counter::value_above, 5i64), None);
+}
+
+#[test]
+fn test_undecodable_arguments_abort_the_call() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+    let calls = *dusk_core::abi::CALLS.lock().unwrap();
+
+    // `add` decodes `(i64, i64)`, so the decoding fails in `wrap_call`
+    let result = std::panic::catch_unwind(|| call::<_, ()>(counter::add, 1u8));
+
+    let panic = result.unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("Argument should correctly deserialize"));
+    assert_eq!(*dusk_core::abi::CALLS.lock().unwrap(), calls);
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 5);
+}
+
+#[test]
+#[should_panic(expected = "reverted with code 3 at 5")]
+fn test_diverging_method_aborts_the_call() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    call::<_, ()>(counter::revert, 3u32);
+}
+
+#[test]
+fn test_deposit_is_left_unclaimed_by_methods_that_are_not_payable() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    *dusk_core::abi::DEPOSIT.lock().unwrap() = 3;
+    call::<_, ()>(counter::add, (1i64, 2i64));
+    let deposit = std::mem::take(&mut *dusk_core::abi::DEPOSIT.lock().unwrap());
+
+    assert_eq!(deposit, 3);
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 8);
+}
+
+#[test]
+fn test_payable_method_claims_deposit() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    *dusk_core::abi::DEPOSIT.lock().unwrap() = 4;
+    call::<_, ()>(counter::stake, 4u64);
+
+    assert_eq!(*dusk_core::abi::DEPOSIT.lock().unwrap(), 0);
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 9);
+}
+
+#[test]
+fn test_payable_method_aborts_without_deposit() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    let result = std::panic::catch_unwind(|| call::<_, ()>(counter::stake, 4u64));
+
+    assert!(result.is_err());
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 5);
+}
+
+#[test]
+fn test_deposit_query_aborts_methods_that_are_not_payable() {
+    let _contract = lock_contract();
+
+    *dusk_core::abi::DEPOSIT.lock().unwrap() = 3;
+    let result = std::panic::catch_unwind(|| call::<_, u64>(vault::vault_balance, ()));
+    *dusk_core::abi::DEPOSIT.lock().unwrap() = 0;
+
+    assert!(result.is_err());
+    assert!(dusk_core::abi::ARGUMENTS.lock().unwrap().take().is_some());
+}
+
+#[test]
+fn test_static_method_is_called_without_state() {
+    let _contract = lock_contract();
+    let calls = *dusk_core::abi::CALLS.lock().unwrap();
+
+    assert_eq!(call::<_, i64>(counter::double, 21i64), 42);
+    assert_eq!(*dusk_core::abi::CALLS.lock().unwrap(), calls + 1);
+}
+
+#[test]
+fn test_state_is_passed_to_associated_function() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 5i64);
+
+    assert_eq!(call::<_, i64>(counter::scaled, 3i64), 15);
+}
+
+#[test]
+fn test_array_arguments_are_decoded() {
+    let _contract = lock_contract();
+
+    let checksum = call::<_, u64>(counter::checksum, ([1u8; 32], [2u8; 64]));
+
+    assert_eq!(checksum, 32 + 128);
+}
+
+#[test]
+fn test_state_with_phantom_data_is_inlined() {
+    let _contract = lock_contract();
+
+    assert_eq!(call::<_, u64>(tagged::tagged_supply, ()), 21);
+}
+
+#[test]
+fn test_state_initializer_uses_module_constants() {
+    let _contract = lock_contract();
+
+    assert_eq!(call::<_, u64>(tagged::tagged_cap, ()), 1000);
+}
+
+#[test]
+fn test_stateless_contract_calls_static_method() {
+    let _contract = lock_contract();
+
+    assert_eq!(call::<_, u64>(hasher::digest, vec![1u8, 2, 3]), 6);
+}
+
+#[test]
+fn test_reexported_state_is_used_by_wrappers() {
+    let _contract = lock_contract();
+
+    assert_eq!(call::<_, u64>(vault::vault_balance, ()), 30);
+}
+
+#[test]
+fn test_module_type_alias_is_decoded_and_returned() {
+    let _contract = lock_contract();
+
+    assert_eq!(call::<_, tagged::Supply>(tagged::tagged_above, 20u64), 1);
+}
+
+#[test]
+fn test_shared_argument_type_is_decoded_by_each_contract() {
+    let _contract = lock_contract();
+    call::<_, ()>(counter::init, 1i64);
+
+    call::<_, ()>(counter::deposit, types::Amount(2));
+    let headroom = call::<_, i64>(tagged::tagged_headroom, types::Amount(9));
+
+    assert_eq!(call::<_, i64>(counter::read_value, ()), 3);
+    assert_eq!(headroom, 1000 - 21 - 9);
+}
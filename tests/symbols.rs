@@ -0,0 +1,90 @@
+//! Checks that the wrappers generated by `#[contract]` are exported as linkable symbols,
+//! even when the contract module isn't reachable from the crate root.
+
+/// A stand-in for `dusk_core::abi`, as `wrap_call` is only available in contracts
+/// compiled for the Dusk VM.
+mod dusk_core {
+    pub mod abi {
+        pub fn wrap_call<A: Default, R, F: Fn(A) -> R>(arg_len: u32, f: F) -> u32 {
+            f(A::default());
+            arg_len
+        }
+    }
+}
+
+mod contracts {
+    #[dusk_forge::contract]
+    mod counter {
+        #![allow(dead_code)]
+
+        use crate::dusk_core;
+
+        pub struct Counter {
+            value: i64,
+        }
+
+        impl Counter {
+            pub const fn new() -> Self {
+                Self { value: 0 }
+            }
+
+            pub fn increment(&mut self) {
+                self.value += 1;
+            }
+
+            pub fn add(&mut self, value: i64) {
+                self.value += value;
+            }
+
+            pub fn read_value(&self) -> i64 {
+                self.value
+            }
+
+            pub fn zero() -> i64 {
+                0
+            }
+
+            pub fn pair(&self) -> (i64, bool) {
+                (self.value, true)
+            }
+
+            pub fn single(&self, value: i64) -> (i64,) {
+                (value,)
+            }
+        }
+    }
+}
+
+// Resolved by the linker against the `no_mangle` symbols of the private module
+extern "Rust" {
+    fn increment(arg_len: u32) -> u32;
+    fn add(arg_len: u32) -> u32;
+    fn read_value(arg_len: u32) -> u32;
+    fn zero(arg_len: u32) -> u32;
+    fn pair(arg_len: u32) -> u32;
+    fn single(arg_len: u32) -> u32;
+}
+
+#[test]
+fn test_wrappers_of_private_module_are_linked() {
+    unsafe {
+        assert_eq!(increment(0), 0);
+        assert_eq!(read_value(0), 0);
+    }
+}
+
+#[test]
+fn test_wrappers_decode_zero_and_single_arguments() {
+    unsafe {
+        assert_eq!(zero(0), 0);
+        assert_eq!(add(8), 8);
+    }
+}
+
+#[test]
+fn test_wrappers_return_tuples() {
+    unsafe {
+        assert_eq!(pair(0), 0);
+        assert_eq!(single(8), 8);
+    }
+}